@@ -0,0 +1,70 @@
+use std::io;
+use v_common::az_impl::az_lmdb::LmdbAzContext;
+use v_common::v_authorization::common::{Access, AuthorizationContext, Trace};
+
+// Authorization context used by the callbacks: access rights and group membership
+pub trait AzContext: AuthorizationContext {
+    // Groups the uri belongs to, directly or through other groups, without the uri itself.
+    // By default they are taken from the group lines of an authorization trace.
+    fn get_groups(&mut self, uri: &str) -> io::Result<Vec<String>> {
+        get_groups_from_trace(self, uri)
+    }
+}
+
+impl AzContext for LmdbAzContext {}
+
+pub fn get_groups_from_trace<A: AuthorizationContext + ?Sized>(az: &mut A, uri: &str) -> io::Result<Vec<String>> {
+    let mut acl = String::new();
+    let mut group = String::new();
+    let mut info = String::new();
+
+    let mut trace = Trace {
+        acl: &mut acl,
+        is_acl: false,
+        group: &mut group,
+        is_group: true,
+        info: &mut info,
+        is_info: false,
+        str_num: 0,
+    };
+
+    // the uri as the object of the request, its groups are traced
+    az.authorize_and_trace(uri, uri, Access::CanRead as u8, false, &mut trace)?;
+
+    let mut res: Vec<String> = vec![];
+    for el in group.split('\n') {
+        let n = el.trim();
+        if !n.is_empty() && n != uri && !res.iter().any(|g| g == n) {
+            res.push(n.to_owned());
+        }
+    }
+    Ok(res)
+}
+
+// A line of the ACL trace: the right `predicate` of `subject` on `object`
+#[derive(PartialEq, Debug)]
+pub struct AclTraceLine<'a> {
+    pub object: &'a str,
+    pub subject: &'a str,
+    pub predicate: &'a str,
+}
+
+// v_authorization writes ACL trace lines as "object group;subject;access predicate"
+pub fn format_acl_trace_line(object: &str, subject: &str, predicate: &str) -> String {
+    format!("{};{};{}\n", object, subject, predicate)
+}
+
+pub fn parse_acl_trace(acl: &str) -> Vec<AclTraceLine<'_>> {
+    let mut res = vec![];
+    for el in acl.split('\n') {
+        let parts: Vec<&str> = el.trim().split(';').collect();
+        if parts.len() == 3 {
+            res.push(AclTraceLine {
+                object: parts[0],
+                subject: parts[1],
+                predicate: parts[2],
+            });
+        }
+    }
+    res
+}
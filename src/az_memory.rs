@@ -0,0 +1,166 @@
+use crate::az_context::{format_acl_trace_line, AzContext};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use v_common::v_authorization::common::{AuthorizationContext, Trace, ACCESS_8_LIST, ACCESS_PREDICATE_LIST};
+
+// Authorization over rules kept in memory, for tests and tools without the LMDB authorization database
+#[derive(Default)]
+pub struct MemoryAzContext {
+    permissions: HashMap<String, Vec<(String, u8)>>,
+    membership: HashMap<String, Vec<String>>,
+}
+
+impl MemoryAzContext {
+    pub fn add_permission(&mut self, subject: &str, object: &str, access: u8) {
+        self.permissions.entry(object.to_owned()).or_default().push((subject.to_owned(), access));
+    }
+
+    pub fn add_membership(&mut self, member: &str, group: &str) {
+        self.membership.entry(member.to_owned()).or_default().push(group.to_owned());
+    }
+
+    // The uri itself and all groups it belongs to, directly or through other groups
+    fn expand_groups(&self, uri: &str) -> Vec<String> {
+        let mut res = vec![uri.to_owned()];
+        let mut visited: HashSet<String> = HashSet::new();
+        visited.insert(uri.to_owned());
+
+        let mut idx = 0;
+        while idx < res.len() {
+            if let Some(groups) = self.membership.get(&res[idx]) {
+                for g in groups.iter() {
+                    if visited.insert(g.to_owned()) {
+                        res.push(g.to_owned());
+                    }
+                }
+            }
+            idx += 1;
+        }
+
+        res
+    }
+
+    fn authorize_impl(&self, uri: &str, user_uri: &str, request_access: u8, mut trace: Option<&mut Trace>) -> u8 {
+        let subject_groups = self.expand_groups(user_uri);
+        let object_groups = self.expand_groups(uri);
+
+        let mut res = 0;
+        for object in object_groups.iter() {
+            if let Some(t) = trace.as_mut() {
+                if t.is_group && object != uri {
+                    t.group.push_str(&format!("{}\n", object));
+                }
+            }
+
+            if let Some(permissions) = self.permissions.get(object) {
+                for (subject, access) in permissions.iter() {
+                    if !subject_groups.contains(subject) {
+                        continue;
+                    }
+
+                    let granted = access & request_access & !res;
+                    if granted == 0 {
+                        continue;
+                    }
+                    res |= granted;
+
+                    if let Some(t) = trace.as_mut() {
+                        if t.is_acl {
+                            for ch_access in ACCESS_8_LIST {
+                                if granted & ch_access > 0 {
+                                    t.acl.push_str(&format_acl_trace_line(object, subject, ACCESS_PREDICATE_LIST[ch_access as usize]));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(t) = trace.as_mut() {
+            if t.is_info {
+                t.info.push_str(&format!("user {} (groups {:?}), object {} (groups {:?}), request {}, result {}\n", user_uri, subject_groups, uri, object_groups, request_access, res));
+            }
+        }
+
+        res
+    }
+}
+
+impl AuthorizationContext for MemoryAzContext {
+    fn authorize(&mut self, uri: &str, user_uri: &str, request_access: u8, _is_check_for_reload: bool) -> Result<u8, io::Error> {
+        Ok(self.authorize_impl(uri, user_uri, request_access, None))
+    }
+
+    fn authorize_and_trace(&mut self, uri: &str, user_uri: &str, request_access: u8, _is_check_for_reload: bool, trace: &mut Trace) -> Result<u8, io::Error> {
+        Ok(self.authorize_impl(uri, user_uri, request_access, Some(trace)))
+    }
+}
+
+impl AzContext for MemoryAzContext {
+    fn get_groups(&mut self, uri: &str) -> io::Result<Vec<String>> {
+        Ok(self.expand_groups(uri).into_iter().skip(1).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::az_context::{get_groups_from_trace, parse_acl_trace, AclTraceLine};
+    use v_common::v_authorization::common::Access;
+
+    fn az() -> MemoryAzContext {
+        let mut az = MemoryAzContext::default();
+        az.add_membership("d:user_1", "d:editors");
+        az.add_membership("d:editors", "d:staff");
+        az.add_membership("d:staff", "d:editors");
+        az.add_membership("d:user_1", "d:staff");
+        az.add_membership("d:user_2", "d:guests");
+        az
+    }
+
+    #[test]
+    fn get_groups_transitive() {
+        let mut az = az();
+        assert_eq!(az.get_groups("d:user_1").unwrap(), vec!["d:editors", "d:staff"]);
+        assert_eq!(az.get_groups("d:user_2").unwrap(), vec!["d:guests"]);
+        assert!(az.get_groups("d:user_3").unwrap().is_empty());
+    }
+
+    #[test]
+    fn acl_trace_object_and_subject() {
+        let mut az = az();
+        az.add_permission("d:editors", "d:doc_1", Access::CanUpdate as u8);
+
+        let mut acl = String::new();
+        let mut group = String::new();
+        let mut info = String::new();
+        let mut trace = Trace {
+            acl: &mut acl,
+            is_acl: true,
+            group: &mut group,
+            is_group: false,
+            info: &mut info,
+            is_info: false,
+            str_num: 0,
+        };
+        az.authorize_and_trace("d:doc_1", "d:user_1", Access::CanUpdate as u8, false, &mut trace).unwrap();
+
+        assert_eq!(
+            parse_acl_trace(&acl),
+            vec![AclTraceLine {
+                object: "d:doc_1",
+                subject: "d:editors",
+                predicate: ACCESS_PREDICATE_LIST[Access::CanUpdate as usize],
+            }]
+        );
+    }
+
+    #[test]
+    fn get_groups_from_trace_same_as_direct() {
+        let mut az = az();
+        for uri in ["d:user_1", "d:user_2", "d:user_3", "d:editors"].iter() {
+            assert_eq!(get_groups_from_trace(&mut az, uri).unwrap(), az.get_groups(uri).unwrap());
+        }
+    }
+}
@@ -1,43 +1,133 @@
+use crate::az_context::{parse_acl_trace, AzContext};
 use crate::common::*;
+use crate::history::HISTORY;
+use crate::individual_reader::read_individual;
+use crate::lazy_individual::{individual2v8proxy, v8proxy2individual};
+use crate::search_provider::search;
 use crate::session_cache::*;
+use crate::ticket_store::read_ticket_user;
+use serde_json::{json, Value as JsonValue};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use v8::{Context, GetPropertyNamesArgs, HandleScope, Local};
 use v_common::az_impl::az_lmdb::LmdbAzContext;
-use v_common::module::module_impl::Module;
-use v_common::module::remote_indv_r_storage::get_individual;
-use v_common::search::common::FTQuery;
-use v_common::search::ft_client::*;
+use v_common::search::common::{FTQuery, QueryResult};
 use v_common::v_api::api_client::IndvOp;
 use v_common::v_api::common_type::ResultCode;
-use v_common::v_authorization::common::{Access, AuthorizationContext, ACCESS_8_LIST, ACCESS_PREDICATE_LIST};
+use v_common::v_authorization::common::{Access, Trace, ACCESS_8_LIST, ACCESS_PREDICATE_LIST};
+use v_individual_model::onto::datatype::Lang;
 use v_individual_model::onto::individual::Individual;
-use v_individual_model::onto::parser::parse_raw;
 
 lazy_static! {
-    static ref AZ: Mutex<RefCell<LmdbAzContext>> = Mutex::new(RefCell::new(LmdbAzContext::new(1000)));
-    static ref FT_CLIENT: Mutex<RefCell<FTClient>> = Mutex::new(RefCell::new(FTClient::new(Module::get_property("ft_query_service_url").unwrap_or_default())));
+    static ref AZ: Mutex<RefCell<Option<Box<dyn AzContext + Send>>>> = Mutex::new(RefCell::new(None));
     pub static ref G_VARS: Mutex<RefCell<CallbackSharedData>> = Mutex::new(RefCell::new(CallbackSharedData::default()));
     pub static ref G_TRANSACTION: Mutex<RefCell<Transaction>> = Mutex::new(RefCell::new(Transaction::default()));
+    static ref TICKET2USER: Mutex<RefCell<HashMap<String, String>>> = Mutex::new(RefCell::new(HashMap::new()));
+}
+
+static QUERY_STRICT: AtomicBool = AtomicBool::new(false);
+static LAZY_GET_INDIVIDUAL: AtomicBool = AtomicBool::new(false);
+
+pub fn set_authorization_context(az: Box<dyn AzContext + Send>) {
+    let mut sh_az = AZ.lock().unwrap();
+    *sh_az.get_mut() = Some(az);
+}
+
+// Without a context set by the embedder the local LMDB authorization database is used
+fn get_az(sh_az: &mut RefCell<Option<Box<dyn AzContext + Send>>>) -> &mut dyn AzContext {
+    sh_az.get_mut().get_or_insert_with(|| Box::new(LmdbAzContext::new(1000))).as_mut()
+}
+
+pub fn set_ticket_user(ticket_id: &str, user_uri: &str) {
+    let mut sh_tickets = TICKET2USER.lock().unwrap();
+    sh_tickets.get_mut().insert(ticket_id.to_owned(), user_uri.to_owned());
+}
+
+pub fn remove_ticket_user(ticket_id: &str) {
+    let mut sh_tickets = TICKET2USER.lock().unwrap();
+    sh_tickets.get_mut().remove(ticket_id);
+}
+
+pub(crate) fn get_ticket_user(ticket_id: &str) -> Option<String> {
+    let mut sh_tickets = TICKET2USER.lock().unwrap();
+    sh_tickets.get_mut().get(ticket_id).cloned()
+}
+
+// User of a ticket registered with set_ticket_user, otherwise of a valid ticket from the ticket store
+pub(crate) fn resolve_ticket_user(ticket_id: &str) -> Result<String, ResultCode> {
+    if let Some(user_id) = get_ticket_user(ticket_id) {
+        return Ok(user_id);
+    }
+    read_ticket_user(ticket_id)
+}
+
+fn is_write_allowed(tnx: &mut Transaction, opt: &IndvOp, indv: &mut Individual, user_id: &str) -> bool {
+    let id = indv.get_id().to_owned();
+
+    let mut sh_az = AZ.lock().unwrap();
+    let az = get_az(&mut sh_az);
+
+    let is_granted = |az: &mut dyn AzContext, uri: &str, access: u8| az.authorize(uri, user_id, access, false).unwrap_or(0) & access > 0;
+
+    if *opt == IndvOp::Remove {
+        return is_granted(az, &id, Access::CanDelete as u8);
+    }
+
+    let mut types = indv.get_literals("rdf:type").unwrap_or_default();
+
+    match read_individual(&id) {
+        Ok(mut stored) => {
+            if !is_granted(az, &id, Access::CanUpdate as u8) {
+                return false;
+            }
+            if *opt == IndvOp::RemoveFrom {
+                return true;
+            }
+
+            // a class added to a stored individual needs CanCreate as for a new individual
+            let stored_types = stored.get_literals("rdf:type").unwrap_or_default();
+            return types.iter().filter(|t| !stored_types.contains(t)).all(|t| is_granted(az, t, Access::CanCreate as u8));
+        },
+        Err(ResultCode::NotFound) => {},
+        Err(rc) => {
+            // the individual may exist, without it the update rights cannot be checked
+            warn!("is_write_allowed: fail read {}, result code = {:?}", id, rc);
+            return false;
+        },
+    }
+
+    // a new individual, the user must be able to create all its types
+    if types.is_empty() {
+        if let Some(prev_indv) = tnx.get_indv(&id) {
+            types = prev_indv.get_literals("rdf:type").unwrap_or_default();
+        }
+    }
+
+    !types.is_empty() && types.iter().all(|t| is_granted(az, t, Access::CanCreate as u8))
 }
 
 pub fn fn_callback_get_rights(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
-    //let ticket = get_string_arg(scope, &args, 0, "callback_get_rights: ticket not found or invalid").unwrap_or_default();
     let id = get_string_arg(scope, &args, 1, None).unwrap_or_default();
-    let user_id = if let Some(u) = get_string_arg(scope, &args, 2, None) {
+    let user_id = if let Some(u) = get_user_arg(scope, &args, 2) {
         u
     } else {
-        let key = v8::String::new(scope, "user_uri").unwrap();
-        let user_uri = scope.get_current_context().global(scope).get(scope, key.into()).unwrap().to_string(scope).unwrap();
-        user_uri.to_rust_string_lossy(scope)
+        return;
+    };
+    let access = if let Some(a) = get_access_arg(scope, &args, 3, ACCESS_CRUD) {
+        a
+    } else {
+        return;
     };
 
-    debug!("user_id={}, doc_id={}", user_id, id);
+    debug!("user_id={}, doc_id={}, access={}", user_id, id, access);
 
     let mut sh_az = AZ.lock().unwrap();
-    let az = sh_az.get_mut();
+    let az = get_az(&mut sh_az);
 
-    let rights = az.authorize(&id, &user_id, Access::CanRead as u8 | Access::CanCreate as u8 | Access::CanDelete as u8 | Access::CanUpdate as u8, false).unwrap_or(0);
+    let rights = az.authorize(&id, &user_id, access, false).unwrap_or(0);
 
     let mut pstm = Individual::default();
     pstm.set_id("_");
@@ -45,6 +135,8 @@ pub fn fn_callback_get_rights(scope: &mut v8::HandleScope, args: v8::FunctionCal
     for ch_access in ACCESS_8_LIST {
         if rights & ch_access > 0 {
             pstm.add_bool(ACCESS_PREDICATE_LIST[ch_access as usize], rights & ch_access > 0);
+        } else if access & ch_access > 0 {
+            pstm.add_uri("v-s:deniedAccess", ACCESS_PREDICATE_LIST[ch_access as usize]);
         }
     }
     let j_indv = individual2v8obj(scope, pstm.parse_all());
@@ -53,6 +145,202 @@ pub fn fn_callback_get_rights(scope: &mut v8::HandleScope, args: v8::FunctionCal
     drop(sh_az);
 }
 
+const ACCESS_CRUD: u8 = Access::CanRead as u8 | Access::CanCreate as u8 | Access::CanDelete as u8 | Access::CanUpdate as u8;
+
+fn authorize_batch(ids: &[String], user_id: &str, access: u8) -> Vec<u8> {
+    let mut sh_az = AZ.lock().unwrap();
+    let az = get_az(&mut sh_az);
+
+    let res = ids.iter().map(|id| az.authorize(id, user_id, access, false).unwrap_or(0)).collect();
+
+    drop(sh_az);
+    res
+}
+
+pub fn fn_callback_get_rights_batch(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let ids = if let Some(ids) = get_string_array_arg(scope, &args, 1) {
+        ids
+    } else {
+        throw_type_error(scope, "callback_get_rights_batch: arg1 [ids] is not an array");
+        return;
+    };
+    let user_id = if let Some(u) = get_user_arg(scope, &args, 2) {
+        u
+    } else {
+        return;
+    };
+
+    debug!("user_id={}, doc_ids={:?}", user_id, ids);
+
+    let rights = authorize_batch(&ids, &user_id, ACCESS_CRUD);
+
+    let j_res = v8::Object::new(scope);
+    for (id, r) in ids.iter().zip(rights.iter()) {
+        let j_key = str_2_v8(scope, id);
+        let j_val = v8::Integer::new(scope, *r as i32);
+        j_res.set(scope, j_key.into(), j_val.into());
+    }
+    rv.set(j_res.into());
+}
+
+pub fn fn_callback_filter_readable(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let ids = if let Some(ids) = get_string_array_arg(scope, &args, 1) {
+        ids
+    } else {
+        throw_type_error(scope, "callback_filter_readable: arg1 [ids] is not an array");
+        return;
+    };
+    let user_id = if let Some(u) = get_user_arg(scope, &args, 2) {
+        u
+    } else {
+        return;
+    };
+    let access = if let Some(a) = get_access_arg(scope, &args, 3, Access::CanRead as u8) {
+        a
+    } else {
+        return;
+    };
+
+    let rights = authorize_batch(&ids, &user_id, access);
+
+    let j_res = v8::Array::new(scope, 0);
+    let mut j_idx = 0;
+    for (id, r) in ids.iter().zip(rights.iter()) {
+        if r & access == access {
+            let j_key = v8::Integer::new(scope, j_idx);
+            let j_val = str_2_v8(scope, id);
+            j_res.set(scope, j_key.into(), j_val.into());
+            j_idx += 1;
+        }
+    }
+    rv.set(j_res.into());
+}
+
+pub fn fn_callback_get_rights_origin(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let id = get_string_arg(scope, &args, 1, Some("callback_get_rights_origin: arg1 [id] not found or invalid")).unwrap_or_default();
+    let user_id = if let Some(u) = get_user_arg(scope, &args, 2) {
+        u
+    } else {
+        return;
+    };
+
+    let mut acl = String::new();
+    let mut group = String::new();
+    let mut info = String::new();
+
+    let mut trace = Trace {
+        acl: &mut acl,
+        is_acl: true,
+        group: &mut group,
+        is_group: true,
+        info: &mut info,
+        is_info: true,
+        str_num: 0,
+    };
+
+    let mut sh_az = AZ.lock().unwrap();
+    let az = get_az(&mut sh_az);
+    let rights = az.authorize_and_trace(&id, &user_id, ACCESS_CRUD, false, &mut trace).unwrap_or(0);
+    drop(sh_az);
+
+    let mut res = vec![];
+
+    for line in parse_acl_trace(&acl) {
+        let mut pstm = Individual::default();
+        pstm.set_id("_");
+        pstm.add_uri("rdf:type", "v-s:PermissionStatement");
+        pstm.add_uri("v-s:permissionSubject", line.subject);
+        pstm.add_uri("v-s:permissionObject", line.object);
+        pstm.add_bool(line.predicate, true);
+        res.push(pstm);
+    }
+
+    let mut mbshp = Individual::default();
+    mbshp.set_id("_");
+    mbshp.add_uri("rdf:type", "v-s:Membership");
+    mbshp.add_uri("v-s:resource", &id);
+    for el in group.split('\n') {
+        let n = el.trim();
+        if !n.is_empty() {
+            mbshp.add_uri("v-s:memberOf", n);
+        }
+    }
+    res.push(mbshp);
+
+    let mut summary = Individual::default();
+    summary.set_id("_");
+    summary.add_uri("rdf:type", "v-s:PermissionStatement");
+    summary.add_uri("v-s:permissionSubject", &user_id);
+    summary.add_uri("v-s:permissionObject", &id);
+    for ch_access in ACCESS_8_LIST {
+        if ACCESS_CRUD & ch_access > 0 {
+            summary.add_bool(ACCESS_PREDICATE_LIST[ch_access as usize], rights & ch_access > 0);
+        }
+    }
+    summary.add_string("rdfs:comment", &info, Lang::none());
+    res.push(summary);
+
+    let j_res = v8::Array::new(scope, res.len() as i32);
+    for (idx, indv) in res.iter_mut().enumerate() {
+        let j_idx = v8::Integer::new(scope, idx as i32);
+        let j_indv = individual2v8obj(scope, indv.parse_all());
+        j_res.set(scope, j_idx.into(), j_indv.into());
+    }
+    rv.set(j_res.into());
+}
+
+// Groups the user belongs to, directly or through other groups
+fn get_user_groups(user_id: &str) -> Vec<String> {
+    let mut sh_az = AZ.lock().unwrap();
+    let az = get_az(&mut sh_az);
+    match az.get_groups(user_id) {
+        Ok(groups) => groups,
+        Err(e) => {
+            error!("fail read groups of {}, err={:?}", user_id, e);
+            vec![]
+        },
+    }
+}
+
+pub fn fn_callback_get_groups(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let user_id = if let Some(u) = get_user_arg(scope, &args, 1) {
+        u
+    } else {
+        return;
+    };
+
+    let groups = get_user_groups(&user_id);
+
+    let j_res = v8::Array::new(scope, groups.len() as i32);
+    for (idx, g) in groups.iter().enumerate() {
+        let j_idx = v8::Integer::new(scope, idx as i32);
+        let j_val = str_2_v8(scope, g);
+        j_res.set(scope, j_idx.into(), j_val.into());
+    }
+    rv.set(j_res.into());
+}
+
+pub fn fn_callback_is_member_of(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let user_id = if let Some(u) = get_user_arg(scope, &args, 1) {
+        u
+    } else {
+        return;
+    };
+    let group_id = if let Some(g) = get_string_arg(scope, &args, 2, Some("callback_is_member_of: arg2 [group] not found or invalid")) {
+        g
+    } else {
+        return;
+    };
+
+    let is_member = get_user_groups(&user_id).contains(&group_id);
+    rv.set(v8::Boolean::new(scope, is_member).into());
+}
+
+// get_individual returns Proxy objects that convert predicates on access, see lazy_individual
+pub fn set_lazy_get_individual(is_lazy: bool) {
+    LAZY_GET_INDIVIDUAL.store(is_lazy, Ordering::Relaxed);
+}
+
 pub fn fn_callback_get_individual(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
     let id = get_string_arg(scope, &args, 1, Some("callback_get_individual: id not found or invalid")).unwrap_or_default();
 
@@ -71,24 +359,34 @@ pub fn fn_callback_get_individual(scope: &mut v8::HandleScope, args: v8::Functio
         let mut sh_tnx = G_TRANSACTION.lock().unwrap();
         let tnx = sh_tnx.get_mut();
 
+        let is_lazy = LAZY_GET_INDIVIDUAL.load(Ordering::Relaxed);
+
         if let Some(indv) = tnx.get_indv(&id) {
-            let j_indv = individual2v8obj(scope, indv);
-            rv.set(j_indv.into());
+            if is_lazy {
+                let j_indv = individual2v8proxy(scope, Individual::new_from_obj(indv.get_obj()));
+                rv.set(j_indv.into());
+            } else {
+                let j_indv = individual2v8obj(scope, indv);
+                rv.set(j_indv.into());
+            }
         } else {
-            match get_individual(&id) {
-                Some(mut indv) => {
-                    if parse_raw(&mut indv).is_ok() {
-                        let j_indv = individual2v8obj(scope, indv.parse_all());
-                        rv.set(j_indv.into());
-                    } else {
-                        let error_msg = format!("Failed to parse binobj for id: {}", id);
-                        error!("callback_get_individual: {}", error_msg);
-                        let error_string = v8::String::new(scope, &error_msg).unwrap();
-                        let error = v8::Exception::error(scope, error_string);
-                        scope.throw_exception(error);
-                    }
+            match read_individual(&id) {
+                Ok(indv) if is_lazy => {
+                    let j_indv = individual2v8proxy(scope, indv);
+                    rv.set(j_indv.into());
                 },
-                None => {
+                Ok(mut indv) => {
+                    let j_indv = individual2v8obj(scope, &mut indv);
+                    rv.set(j_indv.into());
+                },
+                Err(ResultCode::UnprocessableEntity) => {
+                    let error_msg = format!("Failed to parse binobj for id: {}", id);
+                    error!("callback_get_individual: {}", error_msg);
+                    let error_string = v8::String::new(scope, &error_msg).unwrap();
+                    let error = v8::Exception::error(scope, error_string);
+                    scope.throw_exception(error);
+                },
+                Err(_) => {
                     let warn_msg = format!("Individual not found for id: {}", id);
                     warn!("callback_get_individual: {}", warn_msg);
                     rv.set(v8::undefined(scope).into());
@@ -119,21 +417,20 @@ pub fn fn_callback_get_individuals(scope: &mut v8::HandleScope, args: v8::Functi
                         let j_indv = individual2v8obj(scope, indv);
                         j_res.set(scope, j_idx.into(), j_indv.into());
                     } else {
-                        match get_individual(&id) {
-                            Some(mut indv) => {
-                                if parse_raw(&mut indv).is_ok() {
-                                    let j_indv = individual2v8obj(scope, indv.parse_all());
-                                    j_res.set(scope, j_idx.into(), j_indv.into());
-                                } else {
-                                    let error_msg = format!("Failed to parse binobj for id: {}", id);
-                                    error!("callback_get_individuals: {}", error_msg);
-                                    let error_string = v8::String::new(scope, &error_msg).unwrap();
-                                    let error = v8::Exception::error(scope, error_string);
-                                    scope.throw_exception(error);
-                                    return;
-                                }
+                        match read_individual(&id) {
+                            Ok(mut indv) => {
+                                let j_indv = individual2v8obj(scope, &mut indv);
+                                j_res.set(scope, j_idx.into(), j_indv.into());
+                            },
+                            Err(ResultCode::UnprocessableEntity) => {
+                                let error_msg = format!("Failed to parse binobj for id: {}", id);
+                                error!("callback_get_individuals: {}", error_msg);
+                                let error_string = v8::String::new(scope, &error_msg).unwrap();
+                                let error = v8::Exception::error(scope, error_string);
+                                scope.throw_exception(error);
+                                return;
                             },
-                            None => {
+                            Err(_) => {
                                 warn!("callback_get_individuals: individual not found, id={}", id);
                                 let null_value = v8::null(scope);
                                 j_res.set(scope, j_idx.into(), null_value.into());
@@ -155,6 +452,63 @@ pub fn fn_callback_get_individuals(scope: &mut v8::HandleScope, args: v8::Functi
     rv.set(j_res.into());
 }
 
+pub fn fn_callback_get_individual_version(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let id = get_string_arg(scope, &args, 1, Some("callback_get_individual_version: arg1 [id] not found or invalid")).unwrap_or_default();
+    let update_counter = args.get(2).integer_value(scope);
+    if update_counter.is_none() {
+        warn!("callback_get_individual_version: arg2 [counter] not found or invalid");
+        return;
+    }
+
+    let mut sh_history = HISTORY.lock().unwrap();
+    if sh_history.get_mut().is_none() {
+        drop(sh_history);
+        throw_history_not_configured(scope, "callback_get_individual_version");
+        return;
+    }
+    let history = sh_history.get_mut().as_mut().unwrap();
+
+    if let Some(mut indv) = history.get_version(&id, update_counter.unwrap()) {
+        let j_indv = individual2v8obj(scope, indv.parse_all());
+        rv.set(j_indv.into());
+    } else {
+        rv.set(v8::undefined(scope).into());
+    }
+
+    drop(sh_history);
+}
+
+fn throw_history_not_configured(scope: &mut v8::HandleScope, callback: &str) {
+    let error_msg = "history store is not configured";
+    error!("{}: {}", callback, error_msg);
+    let error_string = v8::String::new(scope, error_msg).unwrap();
+    let error = v8::Exception::error(scope, error_string);
+    scope.throw_exception(error);
+}
+
+pub fn fn_callback_get_individual_history(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let id = get_string_arg(scope, &args, 1, Some("callback_get_individual_history: arg1 [id] not found or invalid")).unwrap_or_default();
+
+    let mut sh_history = HISTORY.lock().unwrap();
+    if sh_history.get_mut().is_none() {
+        drop(sh_history);
+        throw_history_not_configured(scope, "callback_get_individual_history");
+        return;
+    }
+    let history = sh_history.get_mut().as_mut().unwrap();
+
+    let versions = history.get_versions(&id);
+    let j_res = v8::Array::new(scope, versions.len() as i32);
+    for (idx, mut indv) in versions.into_iter().enumerate() {
+        let j_idx = v8::Integer::new(scope, idx as i32);
+        let j_indv = individual2v8obj(scope, indv.parse_all());
+        j_res.set(scope, j_idx.into(), j_indv.into());
+    }
+
+    drop(sh_history);
+    rv.set(j_res.into());
+}
+
 pub fn fn_callback_print(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut _rv: v8::ReturnValue) {
     let mut str_out = String::new();
 
@@ -186,30 +540,88 @@ pub fn fn_callback_get_env_str_var(scope: &mut v8::HandleScope, args: v8::Functi
         drop(sh_g_vars);
     }
 }
-pub fn fn_callback_get_env_num_var(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut _rv: v8::ReturnValue) {
-    if let Some(var_name) = get_string_arg(scope, &args, 0, Some("fn_callback_get_env_str_var: arg not found or invalid")) {
-        //let mut sh_g_vars = G_VARS.lock().unwrap();
-        //let _g_vars = sh_g_vars.get_mut();
+pub fn fn_callback_get_env_num_var(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    if let Some(var_name) = get_string_arg(scope, &args, 0, Some("fn_callback_get_env_num_var: arg not found or invalid")) {
+        let mut sh_g_vars = G_VARS.lock().unwrap();
+        let g_vars = sh_g_vars.get_mut();
 
         debug!("fn_callback_get_env_num_var, var_name={:?}", var_name);
 
-        //if var_name == "$queue_elements_count" || var_name == "$queue_elements_processed" {
-        //    return;
-        //}
+        if let Some(EnvVar::Num(v)) = g_vars.g_key2var.get(&var_name) {
+            let j_res = v8::Number::new(scope, *v);
+            rv.set(j_res.into());
+        }
+
+        drop(sh_g_vars);
     }
 }
 
-pub fn fn_callback_query(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
-    let ticket = get_string_arg(scope, &args, 0, Some("callback_query: arg0 [ticket] not found or invalid"));
-    if ticket.is_none() {
-        return;
+fn env_var2v8<'a>(scope: &mut v8::HandleScope<'a>, var: &mut EnvVar) -> v8::Local<'a, v8::Value> {
+    match var {
+        EnvVar::Str(s) => str_2_v8(scope, s).into(),
+        EnvVar::Num(n) => v8::Number::new(scope, *n).into(),
+        EnvVar::Bool(b) => v8::Boolean::new(scope, *b).into(),
+        EnvVar::StrList(l) => {
+            let j_res = v8::Array::new(scope, l.len() as i32);
+            for (idx, el) in l.iter().enumerate() {
+                let j_idx = v8::Integer::new(scope, idx as i32);
+                let j_el = str_2_v8(scope, el);
+                j_res.set(scope, j_idx.into(), j_el.into());
+            }
+            j_res.into()
+        },
+        EnvVar::Individual(indv) => individual2v8obj(scope, indv.parse_all()).into(),
+        EnvVar::Json(v) => {
+            let j_str = str_2_v8(scope, &v.to_string());
+            v8::json::parse(scope, j_str).unwrap_or_else(|| v8::undefined(scope).into())
+        },
     }
-    let mut ticket = ticket.unwrap();
+}
 
-    let query_str = get_string_arg(scope, &args, 1, Some("callback_query: arg1 [query] not found or invalid"));
-    if query_str.is_none() {
-        return;
+pub fn fn_callback_get_env_var(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    if let Some(var_name) = get_string_arg(scope, &args, 0, Some("fn_callback_get_env_var: arg not found or invalid")) {
+        let mut sh_g_vars = G_VARS.lock().unwrap();
+        let g_vars = sh_g_vars.get_mut();
+
+        debug!("fn_callback_get_env_var, var_name={:?}", var_name);
+
+        if let Some(v) = g_vars.g_key2var.get_mut(&var_name) {
+            let j_res = env_var2v8(scope, v);
+            rv.set(j_res);
+        } else if let Some(v) = g_vars.g_key2attr.get(&var_name) {
+            let j_res = str_2_v8(scope, v);
+            rv.set(j_res.into());
+        } else if let Some(indv) = g_vars.g_key2indv.get_mut(&var_name) {
+            let j_indv = individual2v8obj(scope, indv.parse_all());
+            rv.set(j_indv.into());
+        }
+
+        drop(sh_g_vars);
     }
+}
+
+// Default for the [strict] option of query(): throw QueryError instead of returning a failed result
+pub fn set_query_strict(strict: bool) {
+    QUERY_STRICT.store(strict, Ordering::Relaxed);
+}
+
+struct QueryOptions {
+    query: FTQuery,
+    trace: bool,
+    strict: bool,
+}
+
+fn get_sys_ticket() -> String {
+    let mut sh_tnx = G_TRANSACTION.lock().unwrap();
+    let tnx = sh_tnx.get_mut();
+    let ticket = tnx.sys_ticket.to_owned();
+    drop(sh_tnx);
+    ticket
+}
+
+fn get_query_from_args(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments) -> Option<QueryOptions> {
+    let mut ticket = get_string_arg(scope, args, 0, Some("callback_query: arg0 [ticket] not found or invalid"))?;
+    let query_str = get_string_arg(scope, args, 1, Some("callback_query: arg1 [query] not found or invalid"))?;
 
     let sort;
     let databases;
@@ -218,27 +630,24 @@ pub fn fn_callback_query(scope: &mut v8::HandleScope, args: v8::FunctionCallback
     let from;
 
     if ticket.is_empty() {
-        let mut sh_tnx = G_TRANSACTION.lock().unwrap();
-        let tnx = sh_tnx.get_mut();
-        ticket = tnx.sys_ticket.to_owned();
-        drop(sh_tnx);
+        ticket = get_sys_ticket();
     }
 
-    let mut query = FTQuery::new_with_ticket(&ticket, &query_str.clone().unwrap());
+    let mut query = FTQuery::new_with_ticket(&ticket, &query_str);
     if args.length() > 2 {
-        sort = get_string_arg(scope, &args, 2, Some("callback_query: arg2 [sort] not found or invalid"));
+        sort = get_string_arg(scope, args, 2, Some("callback_query: arg2 [sort] not found or invalid"));
         query.sort = sort.unwrap_or_default();
         if args.length() > 3 {
-            databases = get_string_arg(scope, &args, 3, Some("callback_query: arg3 [databases] not found or invalid"));
+            databases = get_string_arg(scope, args, 3, Some("callback_query: arg3 [databases] not found or invalid"));
             query.databases = databases.unwrap_or_default();
             if args.length() > 4 {
-                top = get_string_i32(scope, &args, 4, Some("callback_query: arg4 [top] not found or invalid"));
+                top = get_string_i32(scope, args, 4, Some("callback_query: arg4 [top] not found or invalid"));
                 query.top = top.unwrap_or(100000);
                 if args.length() > 5 {
-                    limit = get_string_i32(scope, &args, 5, Some("callback_query: arg5 [limit] not found or invalid"));
+                    limit = get_string_i32(scope, args, 5, Some("callback_query: arg5 [limit] not found or invalid"));
                     query.limit = limit.unwrap_or(100000);
                     if args.length() > 6 {
-                        from = get_string_i32(scope, &args, 6, Some("callback_query: arg6 [from] not found or invalid"));
+                        from = get_string_i32(scope, args, 6, Some("callback_query: arg6 [from] not found or invalid"));
                         query.from = from.unwrap_or_default();
                     }
                 }
@@ -246,23 +655,406 @@ pub fn fn_callback_query(scope: &mut v8::HandleScope, args: v8::FunctionCallback
         }
     }
 
-    let _context = scope.get_current_context();
+    Some(QueryOptions {
+        query,
+        trace: false,
+        strict: QUERY_STRICT.load(Ordering::Relaxed),
+    })
+}
+
+fn get_opt_field<'a>(scope: &mut v8::HandleScope<'a>, obj: v8::Local<v8::Object>, name: &str) -> Option<v8::Local<'a, v8::Value>> {
+    let key = str_2_v8(scope, name);
+    obj.get(scope, key.into()).filter(|v| !v.is_undefined())
+}
+
+fn get_opt_str_field(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>, name: &str) -> Result<Option<String>, String> {
+    match get_opt_field(scope, obj, name) {
+        Some(v) if v.is_string() => Ok(Some(v8_2_str(scope, &v))),
+        Some(_) => Err(format!("query: field [{}] must be a string", name)),
+        None => Ok(None),
+    }
+}
+
+fn get_opt_i32_field(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>, name: &str) -> Result<Option<i32>, String> {
+    match get_opt_field(scope, obj, name) {
+        Some(v) if v.is_number() => {
+            let n = v.number_value(scope).unwrap_or(-1.0);
+            if n.fract() != 0.0 || n < 0.0 || n > i32::MAX as f64 {
+                return Err(format!("query: field [{}] must be an integer from 0 to {}", name, i32::MAX));
+            }
+            Ok(Some(n as i32))
+        },
+        Some(_) => Err(format!("query: field [{}] must be a number", name)),
+        None => Ok(None),
+    }
+}
+
+fn get_opt_bool_field(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>, name: &str) -> Result<Option<bool>, String> {
+    match get_opt_field(scope, obj, name) {
+        Some(v) if v.is_boolean() => Ok(Some(v.boolean_value(scope))),
+        Some(_) => Err(format!("query: field [{}] must be a boolean", name)),
+        None => Ok(None),
+    }
+}
+
+// { query, sort, databases, top, limit, from, user, trace, strict }
+fn get_query_from_options(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>) -> Result<QueryOptions, String> {
+    let query_str = get_opt_str_field(scope, obj, "query")?.ok_or_else(|| "query: field [query] is required".to_owned())?;
+
+    let mut query = if let Some(user) = get_opt_str_field(scope, obj, "user")? {
+        FTQuery::new_with_user(&user, &query_str)
+    } else {
+        FTQuery::new_with_ticket(&get_sys_ticket(), &query_str)
+    };
+
+    query.sort = get_opt_str_field(scope, obj, "sort")?.unwrap_or_default();
+    query.databases = get_opt_str_field(scope, obj, "databases")?.unwrap_or_default();
+    query.top = get_opt_i32_field(scope, obj, "top")?.unwrap_or(100000);
+    query.limit = get_opt_i32_field(scope, obj, "limit")?.unwrap_or(100000);
+    query.from = get_opt_i32_field(scope, obj, "from")?.unwrap_or_default();
+
+    Ok(QueryOptions {
+        query,
+        trace: get_opt_bool_field(scope, obj, "trace")?.unwrap_or(false),
+        strict: get_opt_bool_field(scope, obj, "strict")?.unwrap_or_else(|| QUERY_STRICT.load(Ordering::Relaxed)),
+    })
+}
+
+// Uncommitted items go into the first page only (from == 0), so paging does not repeat them,
+// the page is kept within top and limit
+fn merge_own_writes(query_str: &str, from: i32, top: i32, limit: i32, res: &mut QueryResult) {
+    if from != 0 {
+        return;
+    }
 
-    let mut sh_ft_client = FT_CLIENT.lock().unwrap();
-    let ft_client = sh_ft_client.get_mut();
+    let max_len = [top, limit].iter().filter(|n| **n > 0).min().map(|n| *n as usize).unwrap_or(0);
 
-    let res = ft_client.query(query);
+    let mut sh_tnx = G_TRANSACTION.lock().unwrap();
+    let tnx = sh_tnx.get_mut();
+    if tnx.read_own_writes_in_query {
+        tnx.merge_into_query_result(query_str, max_len, res);
+    }
+    drop(sh_tnx);
+}
+
+fn run_query(opts: QueryOptions, is_merge_own_writes: bool) -> QueryResult {
+    let query_str = opts.query.query.clone();
+    let (from, top, limit) = (opts.query.from, opts.query.top, opts.query.limit);
+
+    if opts.trace {
+        info!("query: {}, sort={}, databases={}, top={}, limit={}, from={}", query_str, opts.query.sort, opts.query.databases, top, limit, from);
+    }
 
-    drop(sh_ft_client);
+    let mut res = search(opts.query);
+
+    if is_merge_own_writes {
+        merge_own_writes(&query_str, from, top, limit, &mut res);
+    }
 
     if res.result_code != ResultCode::Ok {
-        warn!("callback query: {}, result code = {:?}", query_str.unwrap(), res.result_code);
+        warn!("callback query: {}, result code = {:?}", query_str, res.result_code);
+    }
+
+    if opts.trace {
+        info!("query: {}, result code = {:?}, count={}, estimated={}, processed={}, total_time={}", query_str, res.result_code, res.count, res.estimated, res.processed, res.total_time);
+    }
+
+    res
+}
+
+pub fn fn_callback_query(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let arg0 = args.get(0);
+
+    let opts = if arg0.is_object() {
+        let obj = arg0.to_object(scope).unwrap();
+        match get_query_from_options(scope, obj) {
+            Ok(opts) => opts,
+            Err(msg) => {
+                throw_type_error(scope, &msg);
+                return;
+            },
+        }
+    } else if let Some(opts) = get_query_from_args(scope, &args) {
+        opts
+    } else {
+        return;
+    };
+
+    let strict = opts.strict;
+    let res = run_query(opts, true);
+
+    if strict && res.result_code != ResultCode::Ok {
+        throw_query_error(scope, res.result_code);
+        return;
     }
-    
+
     let j_res = query_result2v8obj(scope, &res);
     rv.set(j_res.into());
 }
 
+fn throw_query_error(scope: &mut v8::HandleScope, rc: ResultCode) {
+    throw_result_code_error(scope, "QueryError", &format!("query failed, result code = {:?}", rc), rc);
+}
+
+// Error with the given name and a result_code field
+fn throw_result_code_error(scope: &mut v8::HandleScope, name: &str, msg: &str, rc: ResultCode) {
+    let error_string = v8::String::new(scope, msg).unwrap();
+    let error = v8::Exception::error(scope, error_string);
+    if let Some(obj) = error.to_object(scope) {
+        let k_name = str_2_v8(scope, "name").into();
+        let v_name = str_2_v8(scope, name).into();
+        obj.set(scope, k_name, v_name);
+        let k_rc = str_2_v8(scope, "result_code").into();
+        let v_rc = v8::Integer::new(scope, rc as i32).into();
+        obj.set(scope, k_rc, v_rc);
+    }
+    scope.throw_exception(error);
+}
+
+// Same arguments as query(), returns the found individuals the user can read
+pub fn fn_callback_query_individuals(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let arg0 = args.get(0);
+
+    let (opts, user_id) = if arg0.is_object() {
+        let obj = arg0.to_object(scope).unwrap();
+        let opts = match get_query_from_options(scope, obj) {
+            Ok(opts) => opts,
+            Err(msg) => {
+                throw_type_error(scope, &msg);
+                return;
+            },
+        };
+        let user_id = match get_opt_str_field(scope, obj, "user") {
+            Ok(Some(u)) => u,
+            _ => get_current_user(scope),
+        };
+        (opts, user_id)
+    } else if let Some(opts) = get_query_from_args(scope, &args) {
+        if let Some(user_id) = get_user_arg(scope, &args, 7) {
+            (opts, user_id)
+        } else {
+            return;
+        }
+    } else {
+        return;
+    };
+
+    let strict = opts.strict;
+    let res = run_query(opts, true);
+
+    if strict && res.result_code != ResultCode::Ok {
+        throw_query_error(scope, res.result_code);
+        return;
+    }
+
+    let j_res = v8::Array::new(scope, 0);
+    let mut j_idx = 0;
+
+    let mut sh_tnx = G_TRANSACTION.lock().unwrap();
+    let tnx = sh_tnx.get_mut();
+    let mut sh_az = AZ.lock().unwrap();
+    let az = get_az(&mut sh_az);
+
+    for id in res.result.iter() {
+        // items queued in the transaction are returned without authorization, new ones have no rights yet
+        let j_indv = if let Some(indv) = tnx.get_indv(id) {
+            individual2v8obj(scope, indv)
+        } else {
+            if az.authorize(id, &user_id, Access::CanRead as u8, false).unwrap_or(0) & Access::CanRead as u8 == 0 {
+                debug!("callback_query_individuals: skip {}, not readable by {}", id, user_id);
+                continue;
+            }
+
+            match read_individual(id) {
+                Ok(mut indv) => individual2v8obj(scope, &mut indv),
+                Err(e) => {
+                    warn!("callback_query_individuals: fail read {}, err={:?}", id, e);
+                    continue;
+                },
+            }
+        };
+
+        let k = v8::Integer::new(scope, j_idx).into();
+        j_res.set(scope, k, j_indv.into());
+        j_idx += 1;
+    }
+    drop(sh_az);
+    drop(sh_tnx);
+
+    rv.set(j_res.into());
+}
+
+const QUERY_ITER_PAGE_SIZE: i32 = 1000;
+
+fn iter_result<'a>(scope: &mut v8::HandleScope<'a>, value: v8::Local<v8::Value>, done: bool) -> v8::Local<'a, v8::Object> {
+    let res = v8::Object::new(scope);
+    let k_value = str_2_v8(scope, "value").into();
+    res.set(scope, k_value, value);
+    let k_done = str_2_v8(scope, "done").into();
+    let v_done = v8::Boolean::new(scope, done).into();
+    res.set(scope, k_done, v_done);
+    res
+}
+
+// The iterator keeps the query options, the next offset and the current page in private properties
+pub fn fn_callback_query_iter(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let arg0 = args.get(0);
+    if !arg0.is_object() {
+        throw_type_error(scope, "query_iter: arg0 [options] must be an object");
+        return;
+    }
+    let opts_obj = arg0.to_object(scope).unwrap();
+
+    // the options are read once, later changes of the object do not affect the iteration
+    let mut opts = match get_query_from_options(scope, opts_obj) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            throw_type_error(scope, &msg);
+            return;
+        },
+    };
+    if get_opt_field(scope, opts_obj, "top").is_none() {
+        opts.query.top = QUERY_ITER_PAGE_SIZE;
+    }
+
+    let iter = v8::Object::new(scope);
+    let v_opts = str_2_v8(scope, &query_options2json(&opts)).into();
+    set_private(scope, iter, "query_iter_opts", v_opts);
+    let v_from = v8::Number::new(scope, opts.query.from as f64).into();
+    set_private(scope, iter, "query_iter_from", v_from);
+    let v_page = v8::Array::new(scope, 0).into();
+    set_private(scope, iter, "query_iter_page", v_page);
+    let v_idx = v8::Integer::new(scope, 0).into();
+    set_private(scope, iter, "query_iter_idx", v_idx);
+    let v_done = v8::Boolean::new(scope, false).into();
+    set_private(scope, iter, "query_iter_done", v_done);
+
+    let k_next = str_2_v8(scope, "next").into();
+    let f_next = v8::Function::new(scope, fn_query_iter_next).unwrap().into();
+    iter.set(scope, k_next, f_next);
+
+    let k_iterator = v8::Symbol::get_iterator(scope).into();
+    let f_iterator = v8::Function::new(scope, fn_query_iter_self).unwrap().into();
+    iter.set(scope, k_iterator, f_iterator);
+
+    rv.set(iter.into());
+}
+
+// Options of query_iter as parsed at its creation, kept as JSON in a private property of the iterator
+fn query_options2json(opts: &QueryOptions) -> String {
+    let q = &opts.query;
+    json!({
+        "ticket": q.ticket,
+        "user": q.user,
+        "query": q.query,
+        "sort": q.sort,
+        "databases": q.databases,
+        "top": q.top,
+        "limit": q.limit,
+        "trace": opts.trace,
+        "strict": opts.strict,
+    })
+    .to_string()
+}
+
+fn json2query_options(src: &str) -> Option<QueryOptions> {
+    let v: JsonValue = serde_json::from_str(src).ok()?;
+    let query_str = v["query"].as_str()?;
+    let user = v["user"].as_str().unwrap_or_default();
+
+    let mut query = if user.is_empty() {
+        FTQuery::new_with_ticket(v["ticket"].as_str().unwrap_or_default(), query_str)
+    } else {
+        FTQuery::new_with_user(user, query_str)
+    };
+    query.sort = v["sort"].as_str().unwrap_or_default().to_owned();
+    query.databases = v["databases"].as_str().unwrap_or_default().to_owned();
+    query.top = v["top"].as_i64()? as i32;
+    query.limit = v["limit"].as_i64()? as i32;
+
+    Some(QueryOptions {
+        query,
+        trace: v["trace"].as_bool().unwrap_or(false),
+        strict: v["strict"].as_bool().unwrap_or(false),
+    })
+}
+
+fn fn_query_iter_self(_scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    rv.set(args.this().into());
+}
+
+fn fn_query_iter_next(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let iter = args.this();
+
+    loop {
+        let page = get_private(scope, iter, "query_iter_page").and_then(|v| v8::Local::<v8::Array>::try_from(v).ok());
+        let idx = get_private(scope, iter, "query_iter_idx").and_then(|v| v.uint32_value(scope)).unwrap_or_default();
+
+        if let Some(page) = page {
+            if idx < page.length() {
+                let value = page.get_index(scope, idx).unwrap();
+                let v_idx = v8::Integer::new_from_unsigned(scope, idx + 1).into();
+                set_private(scope, iter, "query_iter_idx", v_idx);
+                let res = iter_result(scope, value, false);
+                rv.set(res.into());
+                return;
+            }
+        }
+
+        let is_done = get_private(scope, iter, "query_iter_done").map(|v| v.boolean_value(scope)).unwrap_or(true);
+        let opts = get_private(scope, iter, "query_iter_opts").and_then(|v| json2query_options(&v8_2_str(scope, &v)));
+        if is_done || opts.is_none() {
+            let undef = v8::undefined(scope).into();
+            let res = iter_result(scope, undef, true);
+            rv.set(res.into());
+            return;
+        }
+        let mut opts = opts.unwrap();
+
+        let from = get_private(scope, iter, "query_iter_from").and_then(|v| v.number_value(scope)).unwrap_or_default();
+        opts.query.from = from as i32;
+
+        let strict = opts.strict;
+        let query_str = opts.query.query.clone();
+        let (top, limit) = (opts.query.top, opts.query.limit);
+        let mut res = run_query(opts, false);
+        if res.result_code != ResultCode::Ok {
+            if strict {
+                throw_query_error(scope, res.result_code);
+                return;
+            }
+            let v_done = v8::Boolean::new(scope, true).into();
+            set_private(scope, iter, "query_iter_done", v_done);
+            continue;
+        }
+
+        let next_from = if res.cursor as f64 > from {
+            res.cursor as f64
+        } else {
+            from + res.processed as f64
+        };
+        // a page may be empty when authorization filtered out all its hits, paging goes on by processed
+        let done = res.processed <= 0 || next_from >= res.estimated as f64;
+
+        // after the counters are taken, merged items must not shift the paging
+        merge_own_writes(&query_str, from as i32, top, limit, &mut res);
+
+        let js_page = v8::Array::new(scope, res.result.len() as i32);
+        for (i, el) in res.result.iter().enumerate() {
+            let v = str_2_v8(scope, el).into();
+            js_page.set_index(scope, i as u32, v);
+        }
+
+        set_private(scope, iter, "query_iter_page", js_page.into());
+        let v_idx = v8::Integer::new(scope, 0).into();
+        set_private(scope, iter, "query_iter_idx", v_idx);
+        let v_from = v8::Number::new(scope, next_from).into();
+        set_private(scope, iter, "query_iter_from", v_from);
+        let v_done = v8::Boolean::new(scope, done).into();
+        set_private(scope, iter, "query_iter_done", v_done);
+    }
+}
+
 fn fn_callback_update(opt: IndvOp, scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
     let wticket = get_string_arg(scope, &args, 0, Some("fn_callback_update: arg0 [ticket] not found or invalid"));
     if wticket.is_none() {
@@ -283,6 +1075,8 @@ fn fn_callback_update(opt: IndvOp, scope: &mut v8::HandleScope, args: v8::Functi
         } else {
             error!("callback {:?}, argument is not string", opt);
         }
+    } else if let Some(lazy_indv) = v8proxy2individual(scope, arg1) {
+        indv = lazy_indv;
     } else if arg1.is_object() {
         let js_obj = arg1.to_object(scope).unwrap();
         indv = v8obj2individual(scope, js_obj);
@@ -299,10 +1093,35 @@ fn fn_callback_update(opt: IndvOp, scope: &mut v8::HandleScope, args: v8::Functi
             ticket = tnx.sys_ticket.to_owned();
         }
 
+        if tnx.check_access_on_write {
+            let is_allowed = match resolve_ticket_user(&ticket) {
+                Ok(user_id) => is_write_allowed(tnx, &opt, &mut indv, &user_id),
+                Err(rc) => {
+                    warn!("callback {:?}, ticket is not valid, result code = {:?}, id={}", opt, rc, indv.get_id());
+                    false
+                },
+            };
+
+            if !is_allowed {
+                warn!("callback {:?}, not authorized, id={}", opt, indv.get_id());
+                rv.set(v8::Integer::new(scope, ResultCode::NotAuthorized as i32).into());
+                return;
+            }
+        }
+
         debug!("ADD TO TRANSACTION {:?} {}", &opt, indv.get_id());
         let res = tnx.add_to_transaction(opt, indv, ticket, "".to_string());
         debug!("res={:?}", res);
 
+        if tnx.rc != ResultCode::Ok {
+            drop(sh_tnx);
+            let error_msg = format!("transaction failed, limits exceeded, code={:?}", res);
+            let error_string = v8::String::new(scope, &error_msg).unwrap();
+            let error = v8::Exception::error(scope, error_string);
+            scope.throw_exception(error);
+            return;
+        }
+
         rv.set(v8::Integer::new(scope, res as i32).into());
     } else {
         error!("callback {:?}, invalid argument", opt);
@@ -331,16 +1150,26 @@ pub fn init_context_with_callback<'a>(scope: &mut HandleScope<'a, ()>) -> Local<
     object_templ.set(str_2_v8(scope, "print").into(), v8::FunctionTemplate::new(scope, fn_callback_print).into());
     object_templ.set(str_2_v8(scope, "get_individual").into(), v8::FunctionTemplate::new(scope, fn_callback_get_individual).into());
     object_templ.set(str_2_v8(scope, "get_individuals").into(), v8::FunctionTemplate::new(scope, fn_callback_get_individuals).into());
+    object_templ.set(str_2_v8(scope, "get_individual_version").into(), v8::FunctionTemplate::new(scope, fn_callback_get_individual_version).into());
+    object_templ.set(str_2_v8(scope, "get_individual_history").into(), v8::FunctionTemplate::new(scope, fn_callback_get_individual_history).into());
     object_templ.set(str_2_v8(scope, "put_individual").into(), v8::FunctionTemplate::new(scope, fn_callback_put_individual).into());
     object_templ.set(str_2_v8(scope, "get_env_str_var").into(), v8::FunctionTemplate::new(scope, fn_callback_get_env_str_var).into());
     object_templ.set(str_2_v8(scope, "get_env_num_var").into(), v8::FunctionTemplate::new(scope, fn_callback_get_env_num_var).into());
+    object_templ.set(str_2_v8(scope, "get_env_var").into(), v8::FunctionTemplate::new(scope, fn_callback_get_env_var).into());
     object_templ.set(str_2_v8(scope, "query").into(), v8::FunctionTemplate::new(scope, fn_callback_query).into());
+    object_templ.set(str_2_v8(scope, "query_iter").into(), v8::FunctionTemplate::new(scope, fn_callback_query_iter).into());
+    object_templ.set(str_2_v8(scope, "query_individuals").into(), v8::FunctionTemplate::new(scope, fn_callback_query_individuals).into());
     object_templ.set(str_2_v8(scope, "remove_individual").into(), v8::FunctionTemplate::new(scope, fn_callback_remove_individual).into());
     object_templ.set(str_2_v8(scope, "add_to_individual").into(), v8::FunctionTemplate::new(scope, fn_callback_add_to_individual).into());
     object_templ.set(str_2_v8(scope, "set_in_individual").into(), v8::FunctionTemplate::new(scope, fn_callback_set_in_individual).into());
     object_templ.set(str_2_v8(scope, "remove_from_individual").into(), v8::FunctionTemplate::new(scope, fn_callback_remove_from_individual).into());
     object_templ.set(str_2_v8(scope, "log_trace").into(), v8::FunctionTemplate::new(scope, fn_callback_log_trace).into());
     object_templ.set(str_2_v8(scope, "get_rights").into(), v8::FunctionTemplate::new(scope, fn_callback_get_rights).into());
+    object_templ.set(str_2_v8(scope, "get_rights_batch").into(), v8::FunctionTemplate::new(scope, fn_callback_get_rights_batch).into());
+    object_templ.set(str_2_v8(scope, "get_rights_origin").into(), v8::FunctionTemplate::new(scope, fn_callback_get_rights_origin).into());
+    object_templ.set(str_2_v8(scope, "filter_readable").into(), v8::FunctionTemplate::new(scope, fn_callback_filter_readable).into());
+    object_templ.set(str_2_v8(scope, "get_groups").into(), v8::FunctionTemplate::new(scope, fn_callback_get_groups).into());
+    object_templ.set(str_2_v8(scope, "is_member_of").into(), v8::FunctionTemplate::new(scope, fn_callback_is_member_of).into());
 
     v8::Context::new_from_template(scope, object_templ)
 }
@@ -360,6 +1189,80 @@ fn get_string_arg(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArgume
     None
 }
 
+// User from the argument, or the owner of the ticket in arg0, or the current user of the context if the ticket is empty;
+// throws NotAuthorized for an unknown or expired ticket
+fn get_user_arg(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments, idx: i32) -> Option<String> {
+    if let Some(u) = get_string_arg(scope, args, idx, None) {
+        if !u.is_empty() {
+            return Some(u);
+        }
+    }
+
+    if let Some(ticket) = get_string_arg(scope, args, 0, None) {
+        if !ticket.is_empty() {
+            return match resolve_ticket_user(&ticket) {
+                Ok(u) => Some(u),
+                Err(rc) => {
+                    warn!("ticket is not valid, result code = {:?}", rc);
+                    throw_result_code_error(scope, "NotAuthorized", &format!("ticket is not valid, result code = {:?}", rc), rc);
+                    None
+                },
+            };
+        }
+    }
+
+    Some(get_current_user(scope))
+}
+
+fn get_current_user(scope: &mut v8::HandleScope) -> String {
+    let key = v8::String::new(scope, "user_uri").unwrap();
+    let user_uri = scope.get_current_context().global(scope).get(scope, key.into()).unwrap().to_string(scope).unwrap();
+    user_uri.to_rust_string_lossy(scope)
+}
+
+// Access mask from the argument, the default if it is not given; throws TypeError for a mask
+// that is not a combination of the CRUD bits
+fn get_access_arg(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments, idx: i32, default: u8) -> Option<u8> {
+    let arg = args.get(idx);
+    if arg.is_null_or_undefined() {
+        return Some(default);
+    }
+
+    let access = if arg.is_number() {
+        arg.number_value(scope).unwrap_or(-1.0)
+    } else {
+        -1.0
+    };
+    if access.fract() != 0.0 || access < 1.0 || access > ACCESS_CRUD as f64 {
+        throw_type_error(scope, &format!("access mask must be a combination of the CRUD bits (1..{}), got {}", ACCESS_CRUD, v8_2_str(scope, &arg)));
+        return None;
+    }
+    Some(access as u8)
+}
+
+fn get_string_array_arg(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments, idx: i32) -> Option<Vec<String>> {
+    let arg = args.get(idx);
+    if !arg.is_array() {
+        return None;
+    }
+
+    let arr = v8::Local::<v8::Array>::try_from(arg).ok()?;
+    let mut res = vec![];
+    for i in 0..arr.length() {
+        if let Some(v) = arr.get_index(scope, i) {
+            res.push(v8_2_str(scope, &v));
+        }
+    }
+    Some(res)
+}
+
+fn throw_type_error(scope: &mut v8::HandleScope, msg: &str) {
+    error!("{}", msg);
+    let error_string = v8::String::new(scope, msg).unwrap();
+    let error = v8::Exception::type_error(scope, error_string);
+    scope.throw_exception(error);
+}
+
 fn get_string_i32(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments, idx: i32, warn_msg: Option<&str>) -> Option<i32> {
     let arg = args.get(idx).int32_value(scope);
     if arg.is_none() {
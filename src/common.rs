@@ -11,7 +11,7 @@ use v_common::search::common::QueryResult;
 use v_individual_model::onto::datatype::Lang;
 use v_individual_model::onto::individual::Individual;
 use v_individual_model::onto::onto_impl::Onto;
-use v_individual_model::onto::resource::Value;
+use v_individual_model::onto::resource::{Resource, Value};
 
 pub struct HashVec<String> {
     pub hash: HashSet<String>,
@@ -243,9 +243,7 @@ fn add_v8_value_obj_to_individual(
 pub fn query_result2v8obj<'a>(scope: &mut HandleScope<'a>, src: &QueryResult) -> v8::Local<'a, v8::Object> {
     let mut v8_obj = v8::Object::new(scope);
 
-    let v1 = v8::Integer::new(scope, src.count as i32).into();
-    let k1 = str_2_v8(scope, "count").into();
-    v8_obj.set(scope, k1, v1);
+    set_key_num_value(scope, &mut v8_obj, "count", src.count as i64);
 
     let js_resources = v8::Array::new(scope, src.result.len() as i32);
     for (idx, el) in src.result.iter().enumerate() {
@@ -256,80 +254,125 @@ pub fn query_result2v8obj<'a>(scope: &mut HandleScope<'a>, src: &QueryResult) ->
 
     let key = str_2_v8(scope, "result").into();
     v8_obj.set(scope, key, js_resources.into());
-    set_key_i32_value(scope, &mut v8_obj, "estimated", src.estimated as i32);
-    set_key_i32_value(scope, &mut v8_obj, "processed", src.processed as i32);
-    set_key_i32_value(scope, &mut v8_obj, "total_time", src.total_time as i32);
-    set_key_i32_value(scope, &mut v8_obj, "query_time", src.query_time as i32);
-    set_key_i32_value(scope, &mut v8_obj, "authorize_time", src.authorize_time as i32);
+    set_key_num_value(scope, &mut v8_obj, "estimated", src.estimated as i64);
+    set_key_num_value(scope, &mut v8_obj, "processed", src.processed as i64);
+    set_key_num_value(scope, &mut v8_obj, "cursor", src.cursor as i64);
+    set_key_num_value(scope, &mut v8_obj, "total_time", src.total_time as i64);
+    set_key_num_value(scope, &mut v8_obj, "query_time", src.query_time as i64);
+    set_key_num_value(scope, &mut v8_obj, "authorize_time", src.authorize_time as i64);
     set_key_i32_value(scope, &mut v8_obj, "result_code", src.result_code as i32);
     v8_obj
 }
 
-pub fn individual2v8obj<'a>(scope: &mut HandleScope<'a>, src: &mut Individual) -> v8::Local<'a, v8::Object> {
-    let mut v8_obj = v8::Object::new(scope);
+fn unquote(s: &str) -> Option<String> {
+    let s = s.trim();
+    if s.len() >= 2 && ((s.starts_with('\'') && s.ends_with('\'')) || (s.starts_with('"') && s.ends_with('"'))) {
+        let inner = &s[1..s.len() - 1];
+        if !inner.contains(&['\'', '"', '*'][..]) {
+            return Some(inner.to_owned());
+        }
+    }
+    None
+}
 
-    set_key_str_value(scope, &mut v8_obj, "@", src.get_id());
+pub struct SimpleClause {
+    pub predicate: String,
+    pub value: String,
+    // === is an exact match, == is a tokenized match in the search service
+    pub is_exact: bool,
+}
 
-    let map_resources = src.get_obj().get_resources();
-    for (predicate, resources) in map_resources {
-        let js_resources = v8::Array::new(scope, resources.len() as i32);
+// Parses queries of form 'p1' === 'v1' && 'p2' == 'v2', returns None for anything else
+pub fn parse_simple_query(query: &str) -> Option<Vec<SimpleClause>> {
+    if query.contains("||") {
+        return None;
+    }
 
-        for (idx, resource) in resources.iter().enumerate() {
-            let mut v8_value = v8::Object::new(scope);
-            let key = v8::Integer::new(scope, idx as i32).into();
-            js_resources.set(scope, key, v8_value.into());
+    let mut res = vec![];
+    for clause in query.split("&&") {
+        let clause = clause.trim().trim_start_matches('(').trim_end_matches(')');
 
-            match &resource.value {
-                Value::Num(m, e) => {
-                    let scale = if *e < 0 {
-                        (*e * -1) as u32
-                    } else {
-                        0
-                    };
+        let (predicate, value, is_exact) = if let Some(pos) = clause.find("===") {
+            (&clause[..pos], &clause[pos + 3..], true)
+        } else if let Some(pos) = clause.find("==") {
+            (&clause[..pos], &clause[pos + 2..], false)
+        } else {
+            return None;
+        };
+
+        res.push(SimpleClause {
+            predicate: unquote(predicate)?,
+            value: unquote(value)?,
+            is_exact,
+        });
+    }
 
-                    let num = if *e > 0 {
-                        *m * 10_i64.pow(*e as u32)
-                    } else {
-                        *m
-                    };
-
-                    let d = Decimal::new(num, scale);
-
-                    set_key_str_value(scope, &mut v8_value, "data", &d.to_string());
-                    set_key_str_value(scope, &mut v8_value, "type", "Decimal");
-                },
-                Value::Int(i) => {
-                    let i_val = *i;
-                    if i_val < i32::MAX as i64 {
-                        set_key_i32_value(scope, &mut v8_value, "data", i_val as i32);
-                    } else {
-                        set_key_i64_value(scope, &mut v8_value, "data", i_val);
-                    }
-                    set_key_str_value(scope, &mut v8_value, "type", "Integer");
-                },
-                Value::Datetime(i) => {
-                    let dt = *i;
-                    set_key_str_value(scope, &mut v8_value, "data", &format!("{:?}", &Utc.timestamp(dt, 0)));
-                    set_key_str_value(scope, &mut v8_value, "type", "Datetime");
-                },
-                Value::Bool(b) => {
-                    set_key_bool_value(scope, &mut v8_value, "data", *b);
-                    set_key_str_value(scope, &mut v8_value, "type", "Boolean");
-                },
-                Value::Str(s, l) => {
-                    if l.is_some() {
-                        set_key_str_value(scope, &mut v8_value, "lang", &l.to_string().to_uppercase());
-                    }
-                    set_key_str_value(scope, &mut v8_value, "data", s);
-                    set_key_str_value(scope, &mut v8_value, "type", "String");
-                },
-                Value::Uri(s) => {
-                    set_key_str_value(scope, &mut v8_value, "data", s);
-                    set_key_str_value(scope, &mut v8_value, "type", "Uri");
-                },
-                _ => {},
+    if res.is_empty() {
+        None
+    } else {
+        Some(res)
+    }
+}
+
+// Exact equality on every clause; for == clauses this is narrower than the search service
+pub fn is_match_simple_query(indv: &mut Individual, clauses: &[SimpleClause]) -> bool {
+    for c in clauses.iter() {
+        if c.predicate == "@" {
+            if indv.get_id() != c.value {
+                return false;
             }
+        } else if !indv.get_literals(&c.predicate).unwrap_or_default().contains(&c.value) {
+            return false;
         }
+    }
+    true
+}
+
+// True only if the search service can not match the individual: an === clause on the id
+// or on a predicate with Uri values that the individual does not satisfy
+pub fn is_excluded_by_simple_query(indv: &mut Individual, clauses: &[SimpleClause]) -> bool {
+    for c in clauses.iter().filter(|c| c.is_exact) {
+        if c.predicate == "@" {
+            if indv.get_id() != c.value {
+                return true;
+            }
+            continue;
+        }
+
+        if let Some(resources) = indv.get_obj().get_resources().get(&c.predicate) {
+            let is_uri = !resources.is_empty() && resources.iter().all(|r| matches!(r.value, Value::Uri(_)));
+            if is_uri && !resources.iter().any(|r| matches!(&r.value, Value::Uri(u) if *u == c.value)) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+pub fn num2decimal(m: i64, e: i64) -> Decimal {
+    let scale = if e < 0 {
+        (e * -1) as u32
+    } else {
+        0
+    };
+
+    let num = if e > 0 {
+        m * 10_i64.pow(e as u32)
+    } else {
+        m
+    };
+
+    Decimal::new(num, scale)
+}
+
+pub fn individual2v8obj<'a>(scope: &mut HandleScope<'a>, src: &mut Individual) -> v8::Local<'a, v8::Object> {
+    let mut v8_obj = v8::Object::new(scope);
+
+    set_key_str_value(scope, &mut v8_obj, "@", src.get_id());
+
+    let map_resources = src.get_obj().get_resources();
+    for (predicate, resources) in map_resources {
+        let js_resources = resources2v8array(scope, resources);
 
         let key = str_2_v8(scope, predicate).into();
         v8_obj.set(scope, key, js_resources.into());
@@ -338,6 +381,69 @@ pub fn individual2v8obj<'a>(scope: &mut HandleScope<'a>, src: &mut Individual) -
     v8_obj
 }
 
+pub fn resources2v8array<'a>(scope: &mut HandleScope<'a>, resources: &[Resource]) -> v8::Local<'a, v8::Array> {
+    let js_resources = v8::Array::new(scope, resources.len() as i32);
+
+    for (idx, resource) in resources.iter().enumerate() {
+        let mut v8_value = v8::Object::new(scope);
+        let key = v8::Integer::new(scope, idx as i32).into();
+        js_resources.set(scope, key, v8_value.into());
+
+        match &resource.value {
+            Value::Num(m, e) => {
+                let d = num2decimal(*m, *e);
+
+                set_key_str_value(scope, &mut v8_value, "data", &d.to_string());
+                set_key_str_value(scope, &mut v8_value, "type", "Decimal");
+            },
+            Value::Int(i) => {
+                let i_val = *i;
+                if i_val < i32::MAX as i64 {
+                    set_key_i32_value(scope, &mut v8_value, "data", i_val as i32);
+                } else {
+                    set_key_i64_value(scope, &mut v8_value, "data", i_val);
+                }
+                set_key_str_value(scope, &mut v8_value, "type", "Integer");
+            },
+            Value::Datetime(i) => {
+                let dt = *i;
+                set_key_str_value(scope, &mut v8_value, "data", &format!("{:?}", &Utc.timestamp(dt, 0)));
+                set_key_str_value(scope, &mut v8_value, "type", "Datetime");
+            },
+            Value::Bool(b) => {
+                set_key_bool_value(scope, &mut v8_value, "data", *b);
+                set_key_str_value(scope, &mut v8_value, "type", "Boolean");
+            },
+            Value::Str(s, l) => {
+                if l.is_some() {
+                    set_key_str_value(scope, &mut v8_value, "lang", &l.to_string().to_uppercase());
+                }
+                set_key_str_value(scope, &mut v8_value, "data", s);
+                set_key_str_value(scope, &mut v8_value, "type", "String");
+            },
+            Value::Uri(s) => {
+                set_key_str_value(scope, &mut v8_value, "data", s);
+                set_key_str_value(scope, &mut v8_value, "type", "Uri");
+            },
+            _ => {},
+        }
+    }
+
+    js_resources
+}
+
+pub(crate) fn get_private<'a>(scope: &mut HandleScope<'a>, obj: v8::Local<v8::Object>, name: &str) -> Option<v8::Local<'a, v8::Value>> {
+    let name = str_2_v8(scope, name);
+    let key = v8::Private::for_api(scope, Some(name));
+    obj.get_private(scope, key).filter(|v| !v.is_undefined())
+}
+
+pub(crate) fn set_private(scope: &mut HandleScope, obj: v8::Local<v8::Object>, name: &str, value: v8::Local<v8::Value>) {
+    let name = str_2_v8(scope, name);
+    let key = v8::Private::for_api(scope, Some(name));
+    obj.set_private(scope, key, value);
+}
+
 fn set_key_str_value(scope: &mut HandleScope, v8_obj: &mut v8::Local<v8::Object>, key: &str, val: &str) {
     let v8_key = str_2_v8(scope, key).into();
     let v8_val = str_2_v8(scope, val).into();
@@ -356,6 +462,20 @@ fn set_key_i32_value(scope: &mut HandleScope, v8_obj: &mut v8::Local<v8::Object>
     v8_obj.set(scope, v8_key, v8_val);
 }
 
+// Largest integer a JS Number holds exactly
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+// Number if the value fits without loss of precision, BigInt otherwise
+fn set_key_num_value(scope: &mut HandleScope, v8_obj: &mut v8::Local<v8::Object>, key: &str, val: i64) {
+    let v8_key = str_2_v8(scope, key).into();
+    let v8_val = if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&val) {
+        v8::Number::new(scope, val as f64).into()
+    } else {
+        v8::BigInt::new_from_i64(scope, val).into()
+    };
+    v8_obj.set(scope, v8_key, v8_val);
+}
+
 fn set_key_i64_value(scope: &mut HandleScope, v8_obj: &mut v8::Local<v8::Object>, key: &str, val: i64) {
     let v8_key = str_2_v8(scope, key).into();
     let v8_val = v8::BigInt::new_from_i64(scope, val).into();
@@ -468,3 +588,89 @@ pub fn is_filter_pass(script: &ScriptInfo<ScriptInfoContext>, individual_id: &st
 
     is_pass
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person() -> Individual {
+        let mut indv = Individual::default();
+        indv.set_id("d:person_1");
+        indv.add_uri("rdf:type", "v-s:Person");
+        indv.add_string("v-s:name", "John Doe", Lang::none());
+        indv
+    }
+
+    #[test]
+    fn parse_simple_query_exact_and_tokenized() {
+        let clauses = parse_simple_query("'rdf:type' === 'v-s:Person' && 'v-s:name' == 'John'").unwrap();
+        assert_eq!(clauses.len(), 2);
+        assert_eq!(clauses[0].predicate, "rdf:type");
+        assert_eq!(clauses[0].value, "v-s:Person");
+        assert!(clauses[0].is_exact);
+        assert_eq!(clauses[1].predicate, "v-s:name");
+        assert_eq!(clauses[1].value, "John");
+        assert!(!clauses[1].is_exact);
+    }
+
+    #[test]
+    fn parse_simple_query_quotes_and_parentheses() {
+        let clauses = parse_simple_query("('@' === \"d:person_1\") && ( 'rdf:type'==='v-s:Person' )").unwrap();
+        assert_eq!(clauses.len(), 2);
+        assert_eq!(clauses[0].predicate, "@");
+        assert_eq!(clauses[0].value, "d:person_1");
+        assert_eq!(clauses[1].value, "v-s:Person");
+    }
+
+    #[test]
+    fn parse_simple_query_rejects_other_queries() {
+        assert!(parse_simple_query("").is_none());
+        assert!(parse_simple_query("'rdf:type' === 'v-s:Person' || 'rdf:type' === 'v-s:Document'").is_none());
+        assert!(parse_simple_query("'rdf:type' !== 'v-s:Person'").is_none());
+        assert!(parse_simple_query("'v-s:name' == 'Jo*'").is_none());
+        assert!(parse_simple_query("'v-s:date' > '2020-01-01'").is_none());
+        assert!(parse_simple_query("rdf:type === 'v-s:Person'").is_none());
+        assert!(parse_simple_query("'rdf:type' === 'v-s:Person' && ").is_none());
+    }
+
+    #[test]
+    fn is_match_simple_query_all_clauses() {
+        let mut indv = person();
+
+        let clauses = parse_simple_query("'rdf:type' === 'v-s:Person' && 'v-s:name' == 'John Doe'").unwrap();
+        assert!(is_match_simple_query(&mut indv, &clauses));
+
+        let clauses = parse_simple_query("'@' === 'd:person_1'").unwrap();
+        assert!(is_match_simple_query(&mut indv, &clauses));
+
+        let clauses = parse_simple_query("'rdf:type' === 'v-s:Person' && 'v-s:name' == 'John'").unwrap();
+        assert!(!is_match_simple_query(&mut indv, &clauses));
+
+        let clauses = parse_simple_query("'v-s:email' === 'john@example.com'").unwrap();
+        assert!(!is_match_simple_query(&mut indv, &clauses));
+    }
+
+    #[test]
+    fn is_excluded_by_simple_query_only_exact_uri() {
+        let mut indv = person();
+
+        let clauses = parse_simple_query("'rdf:type' === 'v-s:Document'").unwrap();
+        assert!(is_excluded_by_simple_query(&mut indv, &clauses));
+
+        let clauses = parse_simple_query("'@' === 'd:person_2'").unwrap();
+        assert!(is_excluded_by_simple_query(&mut indv, &clauses));
+
+        // tokenized match or subclass may still be found by the search service
+        let clauses = parse_simple_query("'rdf:type' == 'v-s:Document'").unwrap();
+        assert!(!is_excluded_by_simple_query(&mut indv, &clauses));
+
+        let clauses = parse_simple_query("'v-s:name' === 'John'").unwrap();
+        assert!(!is_excluded_by_simple_query(&mut indv, &clauses));
+
+        let clauses = parse_simple_query("'v-s:email' === 'john@example.com'").unwrap();
+        assert!(!is_excluded_by_simple_query(&mut indv, &clauses));
+
+        let clauses = parse_simple_query("'rdf:type' === 'v-s:Person'").unwrap();
+        assert!(!is_excluded_by_simple_query(&mut indv, &clauses));
+    }
+}
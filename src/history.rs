@@ -0,0 +1,110 @@
+use crate::individual_reader::read_individual;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use v_individual_model::onto::individual::Individual;
+
+pub trait IndividualHistory {
+    fn get_version(&mut self, id: &str, update_counter: i64) -> Option<Individual>;
+    fn get_versions(&mut self, id: &str) -> Vec<Individual>;
+}
+
+#[derive(Default)]
+pub struct MemoryHistory {
+    versions: HashMap<String, Vec<Individual>>,
+}
+
+impl MemoryHistory {
+    pub fn add_version(&mut self, indv: &Individual) {
+        let mut version = Individual::new_from_obj(indv.get_obj());
+        version.parse_all();
+        self.versions.entry(version.get_id().to_owned()).or_default().push(version);
+    }
+}
+
+impl IndividualHistory for MemoryHistory {
+    fn get_version(&mut self, id: &str, update_counter: i64) -> Option<Individual> {
+        if let Some(versions) = self.versions.get_mut(id) {
+            for indv in versions.iter_mut() {
+                if indv.get_first_integer("v-s:updateCounter") == Some(update_counter) {
+                    return Some(Individual::new_from_obj(indv.get_obj()));
+                }
+            }
+        }
+        None
+    }
+
+    fn get_versions(&mut self, id: &str) -> Vec<Individual> {
+        let mut res = vec![];
+        if let Some(versions) = self.versions.get_mut(id) {
+            for indv in versions.iter_mut() {
+                res.push((indv.get_first_integer("v-s:updateCounter").unwrap_or_default(), Individual::new_from_obj(indv.get_obj())));
+            }
+        }
+        res.sort_by_key(|(counter, _)| *counter);
+        res.into_iter().map(|(_, indv)| indv).collect()
+    }
+}
+
+// Versions kept in the storage: the stored individual and the individuals reachable from it
+// by v-s:previousVersion links, read with the configured individual reader
+#[derive(Default)]
+pub struct StorageHistory {}
+
+impl StorageHistory {
+    fn read_versions(&self, id: &str) -> Vec<Individual> {
+        let mut res = vec![];
+        let mut visited = HashSet::new();
+        let mut next = Some(id.to_owned());
+
+        while let Some(version_id) = next.take() {
+            if !visited.insert(version_id.clone()) {
+                warn!("history: loop in v-s:previousVersion of {}, at {}", id, version_id);
+                break;
+            }
+
+            match read_individual(&version_id) {
+                Ok(mut indv) => {
+                    next = indv.get_first_literal("v-s:previousVersion");
+                    res.push(indv);
+                },
+                Err(e) => {
+                    if version_id != id {
+                        warn!("history: fail read version {} of {}, err={:?}", version_id, id, e);
+                    }
+                },
+            }
+        }
+
+        res
+    }
+}
+
+impl IndividualHistory for StorageHistory {
+    fn get_version(&mut self, id: &str, update_counter: i64) -> Option<Individual> {
+        self.read_versions(id).into_iter().find_map(|mut indv| {
+            if indv.get_first_integer("v-s:updateCounter") == Some(update_counter) {
+                Some(indv)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn get_versions(&mut self, id: &str) -> Vec<Individual> {
+        let mut res: Vec<(i64, Individual)> =
+            self.read_versions(id).into_iter().map(|mut indv| (indv.get_first_integer("v-s:updateCounter").unwrap_or_default(), indv)).collect();
+        res.sort_by_key(|(counter, _)| *counter);
+        res.into_iter().map(|(_, indv)| indv).collect()
+    }
+}
+
+lazy_static! {
+    pub(crate) static ref HISTORY: Mutex<RefCell<Option<Box<dyn IndividualHistory + Send>>>> = Mutex::new(RefCell::new(None));
+}
+
+// Without a store set by the embedder the history callbacks throw an Error
+pub fn set_individual_history(history: Box<dyn IndividualHistory + Send>) {
+    let mut sh_history = HISTORY.lock().unwrap();
+    *sh_history.get_mut() = Some(history);
+}
@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use v_common::module::remote_indv_r_storage;
+use v_common::v_api::common_type::ResultCode;
+use v_individual_model::onto::individual::Individual;
+use v_individual_model::onto::parser::parse_raw;
+use v_storage::VStorage;
+
+pub trait IndividualReader {
+    // Returns the parsed individual, NotFound or UnprocessableEntity if it could not be parsed
+    fn get_individual(&mut self, id: &str) -> Result<Individual, ResultCode>;
+}
+
+pub struct RemoteIndividualReader {}
+
+impl IndividualReader for RemoteIndividualReader {
+    fn get_individual(&mut self, id: &str) -> Result<Individual, ResultCode> {
+        match remote_indv_r_storage::get_individual(id) {
+            Some(mut indv) => {
+                if parse_raw(&mut indv).is_ok() {
+                    indv.parse_all();
+                    Ok(indv)
+                } else {
+                    Err(ResultCode::UnprocessableEntity)
+                }
+            },
+            None => Err(ResultCode::NotFound),
+        }
+    }
+}
+
+pub struct LmdbIndividualReader {
+    storage: VStorage,
+}
+
+impl LmdbIndividualReader {
+    pub fn new(storage: VStorage) -> Self {
+        Self {
+            storage,
+        }
+    }
+}
+
+impl IndividualReader for LmdbIndividualReader {
+    fn get_individual(&mut self, id: &str) -> Result<Individual, ResultCode> {
+        let mut indv = Individual::default();
+        if !self.storage.get_individual(id, &mut indv) {
+            return Err(ResultCode::NotFound);
+        }
+
+        if parse_raw(&mut indv).is_ok() {
+            indv.parse_all();
+            Ok(indv)
+        } else {
+            Err(ResultCode::UnprocessableEntity)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryIndividualReader {
+    pub individuals: HashMap<String, Individual>,
+}
+
+impl MemoryIndividualReader {
+    pub fn add(&mut self, indv: &Individual) {
+        self.individuals.insert(indv.get_id().to_owned(), Individual::new_from_obj(indv.get_obj()));
+    }
+}
+
+impl IndividualReader for MemoryIndividualReader {
+    fn get_individual(&mut self, id: &str) -> Result<Individual, ResultCode> {
+        match self.individuals.get(id) {
+            Some(indv) => {
+                let mut res = Individual::new_from_obj(indv.get_obj());
+                res.parse_all();
+                Ok(res)
+            },
+            None => Err(ResultCode::NotFound),
+        }
+    }
+}
+
+lazy_static! {
+    static ref INDIVIDUAL_READER: Mutex<RefCell<Box<dyn IndividualReader + Send>>> = Mutex::new(RefCell::new(Box::new(RemoteIndividualReader {})));
+}
+
+pub fn set_individual_reader(reader: Box<dyn IndividualReader + Send>) {
+    let mut sh_reader = INDIVIDUAL_READER.lock().unwrap();
+    *sh_reader.get_mut() = reader;
+}
+
+pub fn read_individual(id: &str) -> Result<Individual, ResultCode> {
+    let mut sh_reader = INDIVIDUAL_READER.lock().unwrap();
+    sh_reader.get_mut().get_individual(id)
+}
+
+// Tests that replace the global reader hold this lock, they run in parallel threads
+#[cfg(test)]
+lazy_static! {
+    pub(crate) static ref TEST_READER_LOCK: Mutex<()> = Mutex::new(());
+}
@@ -0,0 +1,288 @@
+use crate::session_cache::{commit, StoredVersion, Transaction, TransactionItem};
+use crate::transaction_sink::TransactionSink;
+use serde_json::{json, Value};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use v_common::v_api::api_client::IndvOp;
+use v_common::v_api::common_type::ResultCode;
+use v_individual_model::onto::individual::Individual;
+use v_individual_model::onto::json2individual::parse_json_to_individual;
+
+pub struct TransactionJournal {
+    path: PathBuf,
+}
+
+impl TransactionJournal {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+        }
+    }
+
+    pub fn write(&self, tnx: &Transaction) -> io::Result<()> {
+        let mut items = vec![];
+        for ti in tnx.queue.iter() {
+            let mut item = json!({
+                "cmd": ti.cmd.to_i64(),
+                "ticket": ti.ticket_id,
+                "indv": ti.indv.get_obj().as_json(),
+            });
+            // the version the item was based on, so that a replay does the same concurrency check
+            match ti.version {
+                Some(StoredVersion::UpdateCounter(counter)) => item["update_counter"] = json!(counter),
+                Some(StoredVersion::ContentHash(hash)) => item["content_hash"] = json!(hash),
+                None => {},
+            }
+            items.push(item);
+        }
+
+        let record = json!({
+            "id": tnx.id,
+            "event_id": tnx.event_id,
+            "src": tnx.src,
+            "items": items,
+        });
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        // the journal holds tickets, only the owner may read it;
+        // the mode applies to new files only, a temp file left by a crash is removed first
+        if let Err(e) = fs::remove_file(&tmp_path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e);
+            }
+        }
+        let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&tmp_path)?;
+        file.write_all(record.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)
+    }
+
+    pub fn read(&self) -> io::Result<Option<Transaction>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+
+        let record: Value = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+
+        let mut tnx = Transaction::default();
+        tnx.id = record["id"].as_i64().unwrap_or_default();
+        tnx.event_id = record["event_id"].as_str().unwrap_or_default().to_owned();
+        tnx.src = record["src"].as_str().unwrap_or_default().to_owned();
+
+        if let Some(items) = record["items"].as_array() {
+            for item in items {
+                let mut indv = Individual::default();
+                if !parse_json_to_individual(&item["indv"], &mut indv) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid individual in journal {:?}", self.path)));
+                }
+
+                let cmd = IndvOp::from_i64(item["cmd"].as_i64().unwrap_or_default());
+                let mut ti = TransactionItem::new(cmd, indv, item["ticket"].as_str().unwrap_or_default().to_owned());
+                if let Some(counter) = item["update_counter"].as_i64() {
+                    ti.version = Some(StoredVersion::UpdateCounter(counter));
+                } else if let Some(hash) = item["content_hash"].as_u64() {
+                    ti.version = Some(StoredVersion::ContentHash(hash));
+                }
+                tnx.add_item(ti);
+            }
+        }
+
+        Ok(Some(tnx))
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    pub fn recover(&self, sink: &mut dyn TransactionSink) -> Option<ResultCode> {
+        let tnx = match self.read() {
+            Ok(Some(tnx)) => tnx,
+            Ok(None) => return None,
+            Err(e) => {
+                error!("journal: fail read {:?}, err={:?}", self.path, e);
+                return Some(ResultCode::UnprocessableEntity);
+            },
+        };
+
+        warn!("journal: found unfinished transaction, event_id={}, items={}, replay", tnx.event_id, tnx.queue.len());
+
+        let rc = commit(&tnx, sink);
+        if rc == ResultCode::DatabaseModifiedError {
+            // a replay can not succeed, keep the transaction aside for manual handling
+            let failed_path = self.get_failed_path();
+            error!("journal: transaction conflicts with stored data, event_id={}, moved to {:?}", tnx.event_id, failed_path);
+            if let Err(e) = fs::rename(&self.path, &failed_path) {
+                error!("journal: fail move {:?}, err={:?}", self.path, e);
+            }
+        } else if rc != ResultCode::Ok {
+            // keep the journal, the next recover() tries again
+            error!("journal: fail replay transaction, event_id={}, code={:?}, journal is kept", tnx.event_id, rc);
+        } else if let Err(e) = self.clear() {
+            error!("journal: fail clear {:?}, err={:?}", self.path, e);
+        }
+
+        Some(rc)
+    }
+
+    fn get_failed_path(&self) -> PathBuf {
+        let mut failed_path = self.path.clone().into_os_string();
+        failed_path.push(".failed");
+        PathBuf::from(failed_path)
+    }
+}
+
+pub fn commit_with_journal(tnx: &Transaction, sink: &mut dyn TransactionSink, journal: &TransactionJournal) -> ResultCode {
+    if tnx.queue.is_empty() {
+        return commit(tnx, sink);
+    }
+
+    if let Err(e) = journal.write(tnx) {
+        error!("journal: fail write {:?}, err={:?}", journal.path, e);
+        return ResultCode::FailStore;
+    }
+
+    let rc = commit(tnx, sink);
+
+    if let Err(e) = journal.clear() {
+        error!("journal: fail clear {:?}, err={:?}", journal.path, e);
+    }
+
+    rc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::individual_reader::{set_individual_reader, MemoryIndividualReader, TEST_READER_LOCK};
+    use crate::transaction_sink::MemorySink;
+    use std::os::unix::fs::PermissionsExt;
+    use v_individual_model::onto::datatype::Lang;
+
+    struct FailSink {}
+
+    impl TransactionSink for FailSink {
+        fn update(&mut self, _ticket_id: &str, _event_id: &str, _src: &str, _cmd: IndvOp, _indv: &Individual) -> ResultCode {
+            ResultCode::FailStore
+        }
+    }
+
+    fn journal(name: &str) -> TransactionJournal {
+        let path = std::env::temp_dir().join(format!("v8-journal-{}-{}", std::process::id(), name));
+        let journal = TransactionJournal::new(path.to_str().unwrap());
+        journal.clear().unwrap();
+        let _ = fs::remove_file(journal.get_failed_path());
+        journal
+    }
+
+    fn doc(id: &str, counter: i64) -> Individual {
+        let mut indv = Individual::default();
+        indv.set_id(id);
+        indv.add_uri("rdf:type", "v-s:Document");
+        indv.add_string("rdfs:label", "a", Lang::none());
+        indv.add_integer("v-s:updateCounter", counter);
+        indv
+    }
+
+    // one item based on d:doc_1 with update counter 1
+    fn tnx() -> Transaction {
+        let mut tnx = Transaction::default();
+        tnx.id = 7;
+        tnx.event_id = "d:event".to_owned();
+        tnx.src = "test".to_owned();
+        let mut ti = TransactionItem::new(IndvOp::Put, doc("d:doc_1", 1), "ticket_1".to_owned());
+        ti.version = Some(StoredVersion::UpdateCounter(1));
+        tnx.add_item(ti);
+        tnx
+    }
+
+    fn set_stored(indv: &Individual) {
+        let mut reader = MemoryIndividualReader::default();
+        reader.add(indv);
+        set_individual_reader(Box::new(reader));
+    }
+
+    #[test]
+    fn write_read() {
+        let journal = journal("write_read");
+        journal.write(&tnx()).unwrap();
+
+        assert_eq!(fs::metadata(&journal.path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let tnx = journal.read().unwrap().unwrap();
+        assert_eq!(tnx.id, 7);
+        assert_eq!(tnx.event_id, "d:event");
+        assert_eq!(tnx.src, "test");
+        assert_eq!(tnx.queue.len(), 1);
+        let ti = &tnx.queue[0];
+        assert!(ti.cmd == IndvOp::Put);
+        assert_eq!(ti.indv.get_id(), "d:doc_1");
+        assert_eq!(ti.ticket_id, "ticket_1");
+        assert_eq!(ti.version, Some(StoredVersion::UpdateCounter(1)));
+
+        journal.clear().unwrap();
+        assert!(journal.read().unwrap().is_none());
+    }
+
+    #[test]
+    fn recover_without_journal() {
+        let journal = journal("recover_without_journal");
+        assert_eq!(journal.recover(&mut MemorySink::default()), None);
+    }
+
+    #[test]
+    fn recover_replays_and_clears() {
+        let _lock = TEST_READER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_stored(&doc("d:doc_1", 1));
+
+        let journal = journal("recover_replays_and_clears");
+        journal.write(&tnx()).unwrap();
+
+        let mut sink = MemorySink::default();
+        assert_eq!(journal.recover(&mut sink), Some(ResultCode::Ok));
+        assert!(sink.individuals.contains_key("d:doc_1"));
+        assert!(!journal.path.exists());
+    }
+
+    #[test]
+    fn recover_keeps_journal_on_failure() {
+        let _lock = TEST_READER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_stored(&doc("d:doc_1", 1));
+
+        let journal = journal("recover_keeps_journal_on_failure");
+        journal.write(&tnx()).unwrap();
+
+        assert_eq!(journal.recover(&mut FailSink {}), Some(ResultCode::FailStore));
+        assert!(journal.path.exists());
+
+        // the next attempt replays it
+        assert_eq!(journal.recover(&mut MemorySink::default()), Some(ResultCode::Ok));
+        assert!(!journal.path.exists());
+    }
+
+    #[test]
+    fn recover_moves_conflict_to_failed() {
+        let _lock = TEST_READER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_stored(&doc("d:doc_1", 2));
+
+        let journal = journal("recover_moves_conflict_to_failed");
+        journal.write(&tnx()).unwrap();
+
+        let mut sink = MemorySink::default();
+        assert_eq!(journal.recover(&mut sink), Some(ResultCode::DatabaseModifiedError));
+        assert!(sink.individuals.is_empty());
+        assert!(!journal.path.exists());
+
+        let failed_path = journal.get_failed_path();
+        assert!(failed_path.exists());
+        fs::remove_file(failed_path).unwrap();
+    }
+}
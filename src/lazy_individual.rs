@@ -0,0 +1,302 @@
+use crate::common::{get_private, resources2v8array, set_private, str_2_v8, v8_2_str, v8obj_into_individual};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Mutex;
+use v8::HandleScope;
+use v_individual_model::onto::individual::Individual;
+
+// Individuals behind the proxies created in the current execution, by handle;
+// handles are never reused, a proxy kept from an earlier execution finds no individual
+#[derive(Default)]
+struct LazyIndividuals {
+    next_handle: u64,
+    individuals: HashMap<u64, Individual>,
+}
+
+lazy_static! {
+    static ref LAZY_INDIVIDUALS: Mutex<RefCell<LazyIndividuals>> = Mutex::new(RefCell::new(LazyIndividuals::default()));
+}
+
+const HANDLE_KEY: &str = "lazy_individual_handle";
+// object on the target with the arrays already returned to the script, by predicate
+const CACHE_KEY: &str = "lazy_individual_cache";
+
+// Returns a Proxy that converts a predicate only when the script reads it
+// and writes assigned or deleted predicates back to the individual
+pub fn individual2v8proxy<'a>(scope: &mut HandleScope<'a>, mut indv: Individual) -> v8::Local<'a, v8::Proxy> {
+    indv.parse_all();
+
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    let lazy = sh_lazy.get_mut();
+    let handle = lazy.next_handle;
+    lazy.next_handle += 1;
+    lazy.individuals.insert(handle, indv);
+    drop(sh_lazy);
+
+    let target = v8::Object::new(scope);
+    let v_handle = v8::Number::new(scope, handle as f64).into();
+    set_private(scope, target, HANDLE_KEY, v_handle);
+    let cache = v8::Object::new(scope).into();
+    set_private(scope, target, CACHE_KEY, cache);
+
+    let handler = v8::Object::new(scope);
+    set_trap(scope, handler, "get", trap_get);
+    set_trap(scope, handler, "set", trap_set);
+    set_trap(scope, handler, "has", trap_has);
+    set_trap(scope, handler, "ownKeys", trap_own_keys);
+    set_trap(scope, handler, "getOwnPropertyDescriptor", trap_get_own_property_descriptor);
+    set_trap(scope, handler, "deleteProperty", trap_delete_property);
+
+    v8::Proxy::new(scope, target, handler).unwrap()
+}
+
+// Copy of the individual behind a proxy created by individual2v8proxy,
+// arrays the script changed in place are written back first
+pub fn v8proxy2individual(scope: &mut HandleScope, v8_val: v8::Local<v8::Value>) -> Option<Individual> {
+    if !v8_val.is_proxy() {
+        return None;
+    }
+    let proxy = v8::Local::<v8::Proxy>::try_from(v8_val).ok()?;
+    let target = proxy.get_target(scope).to_object(scope)?;
+    let handle = get_handle(scope, target)?;
+
+    write_back_cached_values(scope, target, handle);
+
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    let indv = sh_lazy.get_mut().individuals.get(&handle)?;
+    let mut res = Individual::new_from_obj(indv.get_obj());
+    res.parse_all();
+    Some(res)
+}
+
+// Releases the individuals of all proxies, ExecutionSession calls it when dropped;
+// embedders that create proxies without a session must call it after each execution
+pub fn clear_lazy_individuals() {
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    sh_lazy.get_mut().individuals.clear();
+}
+
+fn set_trap(scope: &mut HandleScope, handler: v8::Local<v8::Object>, name: &str, trap: impl v8::MapFnTo<v8::FunctionCallback>) {
+    let key = str_2_v8(scope, name).into();
+    let func = v8::Function::new(scope, trap).unwrap().into();
+    handler.set(scope, key, func);
+}
+
+fn get_handle(scope: &mut HandleScope, target: v8::Local<v8::Object>) -> Option<u64> {
+    get_private(scope, target, HANDLE_KEY).and_then(|v| v.number_value(scope)).map(|n| n as u64)
+}
+
+fn get_cache<'a>(scope: &mut HandleScope<'a>, target: v8::Local<v8::Object>) -> Option<v8::Local<'a, v8::Object>> {
+    get_private(scope, target, CACHE_KEY).and_then(|v| v.to_object(scope))
+}
+
+// Replaces the values of a predicate, null or undefined removes it
+fn set_predicate_value(scope: &mut HandleScope, indv: &mut Individual, predicate: &str, value: v8::Local<v8::Value>) {
+    indv.remove(predicate);
+    if !value.is_null_or_undefined() {
+        let v8_obj = v8::Object::new(scope);
+        let key = str_2_v8(scope, predicate).into();
+        v8_obj.set(scope, key, value);
+        v8obj_into_individual(scope, v8_obj, indv);
+    }
+}
+
+fn write_back_cached_values(scope: &mut HandleScope, target: v8::Local<v8::Object>, handle: u64) {
+    let cache = if let Some(c) = get_cache(scope, target) {
+        c
+    } else {
+        return;
+    };
+
+    // as in trap_set, the individual is out of the registry while the values are converted
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    let indv = sh_lazy.get_mut().individuals.remove(&handle);
+    drop(sh_lazy);
+
+    let mut indv = if let Some(indv) = indv {
+        indv
+    } else {
+        return;
+    };
+
+    // set and delete drop the cached array, so only present predicates can have one
+    let predicates: Vec<String> = indv.get_obj().get_resources().keys().cloned().collect();
+    for predicate in predicates {
+        let key = str_2_v8(scope, &predicate).into();
+        if let Some(value) = cache.get(scope, key).filter(|v| v.is_array()) {
+            set_predicate_value(scope, &mut indv, &predicate, value);
+        }
+    }
+
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    sh_lazy.get_mut().individuals.insert(handle, indv);
+    drop(sh_lazy);
+}
+
+fn drop_cached_value(scope: &mut HandleScope, target: v8::Local<v8::Value>, predicate: &str) {
+    if let Some(cache) = target.to_object(scope).and_then(|t| get_cache(scope, t)) {
+        let key = str_2_v8(scope, predicate).into();
+        cache.delete(scope, key);
+    }
+}
+
+// (handle, property name) of a trap call, None for symbols and foreign targets
+fn get_trap_args(scope: &mut HandleScope, args: &v8::FunctionCallbackArguments) -> Option<(u64, String)> {
+    let target = args.get(0).to_object(scope)?;
+    let handle = get_handle(scope, target)?;
+    let prop = args.get(1);
+    if prop.is_symbol() {
+        return None;
+    }
+    Some((handle, v8_2_str(scope, &prop)))
+}
+
+// The same array is returned on every read, so that in-place changes are kept
+fn get_predicate_value<'a>(scope: &mut HandleScope<'a>, target: v8::Local<v8::Value>, handle: u64, predicate: &str) -> Option<v8::Local<'a, v8::Value>> {
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    let indv = sh_lazy.get_mut().individuals.get_mut(&handle)?;
+
+    if predicate == "@" {
+        return Some(str_2_v8(scope, indv.get_id()).into());
+    }
+
+    let resources = indv.get_obj().get_resources().get(predicate)?;
+
+    let cache = target.to_object(scope).and_then(|t| get_cache(scope, t))?;
+    let key = str_2_v8(scope, predicate).into();
+    if let Some(value) = cache.get(scope, key).filter(|v| v.is_array()) {
+        return Some(value);
+    }
+
+    let value = resources2v8array(scope, resources).into();
+    cache.set(scope, key, value);
+    Some(value)
+}
+
+fn is_predicate_present(handle: u64, predicate: &str) -> bool {
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    if let Some(indv) = sh_lazy.get_mut().individuals.get_mut(&handle) {
+        return predicate == "@" || indv.get_obj().get_resources().contains_key(predicate);
+    }
+    false
+}
+
+// Names that are not predicates (toString, constructor, symbols) are looked up on the target,
+// so the proxy keeps the behaviour of an ordinary object
+fn trap_get(scope: &mut HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    if let Some((handle, predicate)) = get_trap_args(scope, &args) {
+        if let Some(v) = get_predicate_value(scope, args.get(0), handle, &predicate) {
+            rv.set(v);
+            return;
+        }
+    }
+
+    if let Some(target) = args.get(0).to_object(scope) {
+        let prop = args.get(1);
+        if let Some(v) = target.get(scope, prop) {
+            rv.set(v);
+        }
+    }
+}
+
+fn trap_set(scope: &mut HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let (handle, predicate) = if let Some(a) = get_trap_args(scope, &args) {
+        a
+    } else {
+        rv.set(v8::Boolean::new(scope, false).into());
+        return;
+    };
+    let value = args.get(2);
+
+    // the individual is taken out of the registry while the value is converted,
+    // reading other proxies during the conversion must not block on the registry
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    let indv = sh_lazy.get_mut().individuals.remove(&handle);
+    drop(sh_lazy);
+
+    let mut indv = if let Some(indv) = indv {
+        indv
+    } else {
+        rv.set(v8::Boolean::new(scope, false).into());
+        return;
+    };
+
+    if predicate == "@" {
+        indv.set_id(&v8_2_str(scope, &value));
+    } else {
+        set_predicate_value(scope, &mut indv, &predicate, value);
+        drop_cached_value(scope, args.get(0), &predicate);
+    }
+
+    let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+    sh_lazy.get_mut().individuals.insert(handle, indv);
+    drop(sh_lazy);
+
+    rv.set(v8::Boolean::new(scope, true).into());
+}
+
+fn trap_has(scope: &mut HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let mut is_present = get_trap_args(scope, &args).map(|(handle, predicate)| is_predicate_present(handle, &predicate)).unwrap_or(false);
+    if !is_present {
+        if let Some(target) = args.get(0).to_object(scope) {
+            let prop = args.get(1);
+            is_present = target.has(scope, prop).unwrap_or(false);
+        }
+    }
+    rv.set(v8::Boolean::new(scope, is_present).into());
+}
+
+fn trap_own_keys(scope: &mut HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let mut keys = vec![];
+
+    if let Some(handle) = args.get(0).to_object(scope).and_then(|target| get_handle(scope, target)) {
+        let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+        if let Some(indv) = sh_lazy.get_mut().individuals.get_mut(&handle) {
+            keys.push("@".to_owned());
+            keys.extend(indv.get_obj().get_resources().keys().cloned());
+        }
+    }
+
+    let js_keys = v8::Array::new(scope, keys.len() as i32);
+    for (idx, key) in keys.iter().enumerate() {
+        let v = str_2_v8(scope, key).into();
+        js_keys.set_index(scope, idx as u32, v);
+    }
+    rv.set(js_keys.into());
+}
+
+fn trap_get_own_property_descriptor(scope: &mut HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    let (handle, predicate) = if let Some(a) = get_trap_args(scope, &args) {
+        a
+    } else {
+        return;
+    };
+
+    if let Some(value) = get_predicate_value(scope, args.get(0), handle, &predicate) {
+        // configurable, the target object itself has no such property
+        let desc = v8::Object::new(scope);
+        let k_value = str_2_v8(scope, "value").into();
+        desc.set(scope, k_value, value);
+        for flag in ["writable", "enumerable", "configurable"].iter() {
+            let k = str_2_v8(scope, flag).into();
+            let v = v8::Boolean::new(scope, true).into();
+            desc.set(scope, k, v);
+        }
+        rv.set(desc.into());
+    }
+}
+
+fn trap_delete_property(scope: &mut HandleScope, args: v8::FunctionCallbackArguments, mut rv: v8::ReturnValue) {
+    if let Some((handle, predicate)) = get_trap_args(scope, &args) {
+        if predicate != "@" {
+            let mut sh_lazy = LAZY_INDIVIDUALS.lock().unwrap();
+            if let Some(indv) = sh_lazy.get_mut().individuals.get_mut(&handle) {
+                indv.remove(&predicate);
+            }
+            drop(sh_lazy);
+            drop_cached_value(scope, args.get(0), &predicate);
+        }
+    }
+    rv.set(v8::Boolean::new(scope, true).into());
+}
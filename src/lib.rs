@@ -3,11 +3,20 @@ extern crate lazy_static;
 #[macro_use]
 extern crate log;
 
+pub mod az_context;
+pub mod az_memory;
 pub mod callback;
 pub mod common;
+pub mod history;
+pub mod individual_reader;
+pub mod journal;
 pub mod jsruntime;
+pub mod lazy_individual;
 pub mod scripts_workplace;
+pub mod search_provider;
 pub mod session_cache;
+pub mod ticket_store;
+pub mod transaction_sink;
 
 pub use v8;
 pub use v_common;
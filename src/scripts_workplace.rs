@@ -1,8 +1,7 @@
 use crate::callback::init_context_with_callback;
-use crate::callback::G_VARS;
 use crate::common::*;
 use crate::common::{collect_js_files, collect_module_dirs, str_2_v8};
-use crate::session_cache::CallbackSharedData;
+use crate::session_cache::ExecutionSession;
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
@@ -118,12 +117,7 @@ impl<'a, T: Default> ScriptsWorkPlace<'a, T> {
             }
         }
 
-        let mut session_data = CallbackSharedData::default();
-        session_data.g_key2attr.insert("$ticket".to_owned(), sys_ticket.to_owned());
-        let mut sh_g_vars = G_VARS.lock().unwrap();
-        let g_vars = sh_g_vars.get_mut();
-        *g_vars = session_data;
-        drop(sh_g_vars);
+        let _session = ExecutionSession::new(sys_ticket, "", "");
 
         for x in o_files.iter() {
             match fs::read_to_string(x) {
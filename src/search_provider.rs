@@ -0,0 +1,90 @@
+use crate::common::{is_match_simple_query, parse_simple_query};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use v_common::module::module_impl::Module;
+use v_common::search::common::{FTQuery, QueryResult};
+use v_common::search::ft_client::FTClient;
+use v_common::v_api::common_type::ResultCode;
+use v_individual_model::onto::individual::Individual;
+
+pub trait SearchProvider {
+    fn query(&mut self, query: FTQuery) -> QueryResult;
+}
+
+impl SearchProvider for FTClient {
+    fn query(&mut self, query: FTQuery) -> QueryResult {
+        FTClient::query(self, query)
+    }
+}
+
+// Evaluates simple equality queries ('p1' === 'v1' && ...) over a fixture set, ignores tickets
+#[derive(Default)]
+pub struct MemorySearchProvider {
+    individuals: BTreeMap<String, Individual>,
+}
+
+impl MemorySearchProvider {
+    pub fn add(&mut self, indv: &Individual) {
+        let mut indv = Individual::new_from_obj(indv.get_obj());
+        indv.parse_all();
+        self.individuals.insert(indv.get_id().to_owned(), indv);
+    }
+}
+
+impl SearchProvider for MemorySearchProvider {
+    fn query(&mut self, query: FTQuery) -> QueryResult {
+        let clauses = if let Some(c) = parse_simple_query(&query.query) {
+            c
+        } else {
+            warn!("memory search: query is not supported: {}", query.query);
+            return QueryResult {
+                result_code: ResultCode::NotImplemented,
+                ..Default::default()
+            };
+        };
+
+        let mut found = vec![];
+        for (id, indv) in self.individuals.iter_mut() {
+            if is_match_simple_query(indv, &clauses) {
+                found.push(id.to_owned());
+            }
+        }
+
+        let from = query.from.max(0) as usize;
+        let mut limit = found.len();
+        if query.top > 0 {
+            limit = limit.min(query.top as usize);
+        }
+        if query.limit > 0 {
+            limit = limit.min(query.limit as usize);
+        }
+
+        let result: Vec<String> = found.iter().skip(from).take(limit).cloned().collect();
+
+        QueryResult {
+            count: result.len() as i64,
+            estimated: found.len() as i64,
+            processed: found.len() as i64,
+            cursor: (from + result.len()) as i64,
+            result,
+            result_code: ResultCode::Ok,
+            ..Default::default()
+        }
+    }
+}
+
+lazy_static! {
+    static ref SEARCH_PROVIDER: Mutex<RefCell<Box<dyn SearchProvider + Send>>> =
+        Mutex::new(RefCell::new(Box::new(FTClient::new(Module::get_property("ft_query_service_url").unwrap_or_default()))));
+}
+
+pub fn set_search_provider(provider: Box<dyn SearchProvider + Send>) {
+    let mut sh_provider = SEARCH_PROVIDER.lock().unwrap();
+    *sh_provider.get_mut() = provider;
+}
+
+pub fn search(query: FTQuery) -> QueryResult {
+    let mut sh_provider = SEARCH_PROVIDER.lock().unwrap();
+    sh_provider.get_mut().query(query)
+}
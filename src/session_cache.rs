@@ -1,43 +1,91 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::string::ToString;
 
-use v_common::module::remote_indv_r_storage::get_individual;
+use crate::callback::{get_ticket_user, remove_ticket_user, set_ticket_user, G_VARS};
+use crate::common::{is_excluded_by_simple_query, is_match_simple_query, parse_simple_query};
+use crate::individual_reader::read_individual;
+use crate::lazy_individual::clear_lazy_individuals;
+use crate::transaction_sink::TransactionSink;
 use v_common::module::veda_backend::indv_apply_cmd;
-use v_common::v_api::api_client::{IndvOp, MStorageClient, ALL_MODULES};
+use v_common::search::common::QueryResult;
+use v_common::v_api::api_client::IndvOp;
 use v_common::v_api::common_type::ResultCode;
 use v_individual_model::onto::individual::Individual;
 use v_individual_model::onto::onto_impl::Onto;
-use v_individual_model::onto::parser::parse_raw;
+use v_individual_model::onto::resource::Value;
+
+pub enum EnvVar {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    StrList(Vec<String>),
+    Individual(Individual),
+    Json(serde_json::Value),
+}
+
+pub struct EventHop {
+    pub document: String,
+    pub script: String,
+}
+
+// event_id is a ';'-separated chain of 'document+script' hops, the nearest one first
+pub fn parse_event_chain(event_id: &str) -> Vec<EventHop> {
+    let mut res = vec![];
+
+    for segment in event_id.split(';') {
+        let aa: Vec<&str> = segment.split('+').collect();
+
+        if aa.len() >= 2 {
+            res.push(EventHop {
+                document: aa[0].to_owned(),
+                script: aa[1].to_owned(),
+            });
+        }
+    }
+
+    res
+}
 
 #[derive(Default)]
 pub struct CallbackSharedData {
     pub g_key2indv: HashMap<String, Individual>,
     pub g_key2attr: HashMap<String, String>,
+    pub g_key2var: HashMap<String, EnvVar>,
+    pub event_chain: Vec<EventHop>,
 }
 
 impl CallbackSharedData {
-    pub fn set_g_parent_script_id_etc(&mut self, event_id: &str) {
-        let mut event_id = event_id;
+    pub fn set_var(&mut self, var_name: &str, value: EnvVar) {
+        self.g_key2var.insert(var_name.to_owned(), value);
+    }
 
-        if !event_id.is_empty() {
-            let mut aa: Vec<&str> = event_id.split(';').collect();
-            if !aa.is_empty() {
-                event_id = aa.first().unwrap();
-            }
+    pub fn set_num_var(&mut self, var_name: &str, value: f64) {
+        self.set_var(var_name, EnvVar::Num(value));
+    }
+
+    pub fn set_g_parent_script_id_etc(&mut self, event_id: &str) {
+        self.event_chain = parse_event_chain(event_id);
 
-            aa = event_id.split('+').collect();
+        let is_first_segment_hop = event_id.split(';').next().unwrap_or_default().contains('+');
 
-            if aa.len() >= 2 {
-                self.g_key2attr.insert("$parent_script_id".to_owned(), aa.get(1).unwrap().to_string());
-                self.g_key2attr.insert("$parent_document_id".to_owned(), aa.first().unwrap().to_string());
-            } else {
+        match self.event_chain.first() {
+            Some(hop) if is_first_segment_hop => {
+                self.g_key2attr.insert("$parent_script_id".to_owned(), hop.script.to_owned());
+                self.g_key2attr.insert("$parent_document_id".to_owned(), hop.document.to_owned());
+            },
+            _ => {
                 self.g_key2attr.insert("$parent_script_id".to_owned(), String::default());
                 self.g_key2attr.insert("$parent_document_id".to_owned(), String::default());
-            }
-        } else {
-            self.g_key2attr.insert("$parent_script_id".to_owned(), String::default());
-            self.g_key2attr.insert("$parent_document_id".to_owned(), String::default());
+            },
         }
+
+        let chain: Vec<serde_json::Value> = self.event_chain.iter().map(|hop| serde_json::json!({"document": hop.document, "script": hop.script})).collect();
+        self.set_var("$event_chain", EnvVar::Json(serde_json::Value::Array(chain)));
+    }
+
+    // Returns true if the script already occurs in the event chain max_depth times or more
+    pub fn is_event_loop(&self, script_id: &str, max_depth: usize) -> bool {
+        max_depth > 0 && self.event_chain.iter().filter(|hop| hop.script == script_id).count() >= max_depth
     }
 
     pub fn set_g_super_classes(&mut self, indv_types: &[String], onto: &Onto) {
@@ -47,20 +95,71 @@ impl CallbackSharedData {
             onto.get_supers(indv_type, &mut super_classes);
         }
 
-        let mut g_super_classes = String::new();
+        let g_super_classes: Vec<String> = super_classes.into_iter().collect();
 
-        g_super_classes.push('[');
-        for s in super_classes.iter() {
-            if g_super_classes.len() > 2 {
-                g_super_classes.push(',');
-            }
-            g_super_classes.push('"');
-            g_super_classes.push_str(s);
-            g_super_classes.push('"');
+        self.g_key2attr.insert("$super_classes".to_owned(), serde_json::to_string(&g_super_classes).unwrap_or_default());
+        self.set_var("$super_classes", EnvVar::StrList(g_super_classes));
+    }
+}
+
+// Sets up shared variables for one script run and clears them when dropped
+pub struct ExecutionSession {
+    registered_ticket: Option<String>,
+}
+
+impl ExecutionSession {
+    pub fn new(ticket: &str, user_id: &str, event_id: &str) -> Self {
+        let mut session_data = CallbackSharedData::default();
+        session_data.g_key2attr.insert("$ticket".to_owned(), ticket.to_owned());
+        session_data.g_key2attr.insert("$user".to_owned(), user_id.to_owned());
+        session_data.g_key2attr.insert("$event_id".to_owned(), event_id.to_owned());
+        session_data.set_g_parent_script_id_etc(event_id);
+
+        let mut sh_g_vars = G_VARS.lock().unwrap();
+        *sh_g_vars.get_mut() = session_data;
+        drop(sh_g_vars);
+
+        let mut registered_ticket = None;
+        if !ticket.is_empty() && !user_id.is_empty() && get_ticket_user(ticket).is_none() {
+            set_ticket_user(ticket, user_id);
+            registered_ticket = Some(ticket.to_owned());
+        }
+
+        Self {
+            registered_ticket,
         }
-        g_super_classes.push(']');
+    }
 
-        self.g_key2attr.insert("$super_classes".to_owned(), g_super_classes);
+    pub fn set_document(&self, indv: Individual) {
+        self.set_indv("$document", indv);
+    }
+
+    pub fn set_prev_state(&self, indv: Individual) {
+        self.set_indv("$prev_state", indv);
+    }
+
+    pub fn set_super_classes(&self, indv_types: &[String], onto: &Onto) {
+        let mut sh_g_vars = G_VARS.lock().unwrap();
+        sh_g_vars.get_mut().set_g_super_classes(indv_types, onto);
+    }
+
+    fn set_indv(&self, key: &str, indv: Individual) {
+        let mut sh_g_vars = G_VARS.lock().unwrap();
+        sh_g_vars.get_mut().g_key2indv.insert(key.to_owned(), indv);
+    }
+}
+
+impl Drop for ExecutionSession {
+    fn drop(&mut self) {
+        let mut sh_g_vars = G_VARS.lock().unwrap();
+        *sh_g_vars.get_mut() = CallbackSharedData::default();
+        drop(sh_g_vars);
+
+        clear_lazy_individuals();
+
+        if let Some(ticket) = &self.registered_ticket {
+            remove_ticket_user(ticket);
+        }
     }
 }
 
@@ -68,8 +167,38 @@ pub struct TransactionItem {
     uri: String,
     pub cmd: IndvOp,
     pub indv: Individual,
-    ticket_id: String,
+    pub(crate) ticket_id: String,
     pub rc: ResultCode,
+    pub(crate) version: Option<StoredVersion>,
+}
+
+// Version of the stored individual a queued write was based on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StoredVersion {
+    UpdateCounter(i64),
+    // individuals without v-s:updateCounter are compared by content
+    ContentHash(u64),
+}
+
+pub(crate) fn get_stored_version(indv: &mut Individual) -> StoredVersion {
+    if let Some(counter) = indv.get_first_integer("v-s:updateCounter") {
+        return StoredVersion::UpdateCounter(counter);
+    }
+
+    // sorted predicates serialized to JSON, the hash is kept in journals and must not depend on the build
+    let values: BTreeMap<String, Vec<String>> = individual2values(indv).into_iter().collect();
+    let content = serde_json::to_string(&values).unwrap_or_default();
+    StoredVersion::ContentHash(fnv1a_64(content.as_bytes()))
+}
+
+// 64-bit FNV-1a, a fixed algorithm unlike std's DefaultHasher
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
 }
 
 pub struct Transaction {
@@ -79,6 +208,12 @@ pub struct Transaction {
     buff: HashMap<String, usize>,
     pub queue: Vec<TransactionItem>,
     pub src: String,
+    pub max_items: usize,
+    pub max_size: usize,
+    size: usize,
+    pub rc: ResultCode,
+    pub check_access_on_write: bool,
+    pub read_own_writes_in_query: bool,
 }
 
 impl Default for Transaction {
@@ -90,12 +225,31 @@ impl Default for Transaction {
             buff: Default::default(),
             queue: vec![],
             src: "".to_string(),
+            max_items: 0,
+            max_size: 0,
+            size: 0,
+            rc: ResultCode::Ok,
+            check_access_on_write: false,
+            read_own_writes_in_query: false,
+        }
+    }
+}
+
+impl TransactionItem {
+    pub(crate) fn new(cmd: IndvOp, indv: Individual, ticket_id: String) -> Self {
+        Self {
+            uri: indv.get_id().to_string(),
+            cmd,
+            indv,
+            ticket_id,
+            rc: ResultCode::Ok,
+            version: None,
         }
     }
 }
 
 impl Transaction {
-    fn add_item(&mut self, item: TransactionItem) {
+    pub(crate) fn add_item(&mut self, item: TransactionItem) {
         self.buff.insert(item.uri.clone(), self.queue.len());
         self.queue.push(item);
     }
@@ -110,13 +264,71 @@ impl Transaction {
         None
     }
 
+    // max_len caps the merged result (top/limit of the query), 0 means no cap
+    pub(crate) fn merge_into_query_result(&mut self, query: &str, max_len: usize, res: &mut QueryResult) {
+        if res.result_code != ResultCode::Ok {
+            return;
+        }
+
+        let clauses = if let Some(c) = parse_simple_query(query) {
+            c
+        } else {
+            debug!("query is not simple, uncommitted items are not merged: {}", query);
+            return;
+        };
+
+        let prev_len = res.result.len() as i64;
+
+        for ti in self.queue.iter_mut() {
+            let id = ti.indv.get_id().to_owned();
+            let pos = res.result.iter().position(|x| *x == id);
+
+            if ti.cmd == IndvOp::Remove {
+                if let Some(p) = pos {
+                    res.result.remove(p);
+                }
+            } else if is_match_simple_query(&mut ti.indv, &clauses) {
+                if pos.is_none() {
+                    res.result.push(id);
+                }
+            } else if is_excluded_by_simple_query(&mut ti.indv, &clauses) {
+                // the search service matched the stored version, the queued one can not match
+                if let Some(p) = pos {
+                    res.result.remove(p);
+                }
+            }
+        }
+
+        // estimated counts all matches, count only those returned
+        res.estimated += res.result.len() as i64 - prev_len;
+        if max_len > 0 && res.result.len() > max_len {
+            res.result.truncate(max_len);
+        }
+        res.count += res.result.len() as i64 - prev_len;
+    }
+
+    fn get_version(&self, id: &str) -> Option<StoredVersion> {
+        if let Some(idx) = self.buff.get(id) {
+            if let Some(ti) = self.queue.get(*idx) {
+                return ti.version;
+            }
+        }
+
+        None
+    }
+
     pub(crate) fn add_to_transaction(&mut self, cmd: IndvOp, new_indv: Individual, ticket_id: String, _user_id: String) -> ResultCode {
+        if self.rc != ResultCode::Ok {
+            return self.rc;
+        }
+
         let mut ti = TransactionItem {
             uri: "".to_string(),
             cmd,
             indv: new_indv,
             ticket_id,
             rc: ResultCode::Ok,
+            version: None,
         };
 
         if ti.cmd == IndvOp::Remove {
@@ -125,6 +337,7 @@ impl Transaction {
             ti.uri = ti.indv.get_id().to_string();
 
             if ti.cmd == IndvOp::AddTo || ti.cmd == IndvOp::SetIn || ti.cmd == IndvOp::RemoveFrom {
+                ti.version = self.get_version(ti.indv.get_id());
                 if let Some(prev_indv) = self.get_indv(ti.indv.get_id()) {
                     debug!("{:?} BEFORE: {}", ti.cmd, &prev_indv);
                     debug!("{:?} APPLY: {}", ti.cmd, &ti.indv);
@@ -132,22 +345,18 @@ impl Transaction {
                     debug!("{:?} AFTER: {}", ti.cmd, &prev_indv);
                     ti.indv = Individual::new_from_obj(prev_indv.get_obj());
                 } else {
-                    match get_individual(ti.indv.get_id()) {
-                        Some(mut prev_indv) => {
-                            if parse_raw(&mut prev_indv).is_ok() {
-                                prev_indv.parse_all();
-                                debug!("{:?} BEFORE: {}", ti.cmd, &prev_indv);
-                                debug!("{:?} APPLY: {}", ti.cmd, &ti.indv);
-                                indv_apply_cmd(&ti.cmd, &mut prev_indv, &mut ti.indv);
-                                debug!("{:?} AFTER: {}", ti.cmd, &prev_indv);
-                                ti.indv = prev_indv;
-                            } else {
-                                ti.rc = ResultCode::UnprocessableEntity;
-                            }
+                    match read_individual(ti.indv.get_id()) {
+                        Ok(mut prev_indv) => {
+                            ti.version = Some(get_stored_version(&mut prev_indv));
+                            debug!("{:?} BEFORE: {}", ti.cmd, &prev_indv);
+                            debug!("{:?} APPLY: {}", ti.cmd, &ti.indv);
+                            indv_apply_cmd(&ti.cmd, &mut prev_indv, &mut ti.indv);
+                            debug!("{:?} AFTER: {}", ti.cmd, &prev_indv);
+                            ti.indv = prev_indv;
                         },
-                        None => {
-                            // Individual not found
-                            ti.rc = ResultCode::NotFound;
+                        Err(rc) => {
+                            // Individual not found or failed to parse
+                            ti.rc = rc;
                         },
                     }
                 }
@@ -159,6 +368,21 @@ impl Transaction {
         }
 
         if ti.rc == ResultCode::Ok {
+            if self.max_items > 0 && self.queue.len() >= self.max_items {
+                error!("transaction: limit of {} items exceeded, id={}", self.max_items, ti.indv.get_id());
+                self.rc = ResultCode::SizeTooLarge;
+                return self.rc;
+            }
+
+            if self.max_size > 0 {
+                self.size += ti.indv.get_obj().as_json().to_string().len();
+                if self.size > self.max_size {
+                    error!("transaction: limit of {} bytes exceeded, id={}", self.max_size, ti.indv.get_id());
+                    self.rc = ResultCode::SizeTooLarge;
+                    return self.rc;
+                }
+            }
+
             self.add_item(ti);
             ResultCode::Ok
         } else {
@@ -167,7 +391,26 @@ impl Transaction {
     }
 }
 
-pub fn commit(tnx: &Transaction, api_client: &mut MStorageClient) -> ResultCode {
+fn is_stored_version_changed(id: &str, version: StoredVersion) -> bool {
+    match read_individual(id) {
+        Ok(mut indv) => get_stored_version(&mut indv) != version,
+        Err(_) => true,
+    }
+}
+
+fn is_invalid_id(id: &str) -> bool {
+    id.is_empty() || id.len() < 2
+}
+
+pub fn commit(tnx: &Transaction, sink: &mut dyn TransactionSink) -> ResultCode {
+    if tnx.rc != ResultCode::Ok {
+        return tnx.rc;
+    }
+
+    // all items are checked before the first one is sent, so a conflict leaves the storage untouched;
+    // a repeated id is checked once, its later items are based on the earlier ones of this transaction
+    let mut checked: HashSet<&str> = HashSet::new();
+
     for ti in tnx.queue.iter() {
         if ti.cmd == IndvOp::Remove && ti.indv.get_id().is_empty() {
             continue;
@@ -177,25 +420,280 @@ pub fn commit(tnx: &Transaction, api_client: &mut MStorageClient) -> ResultCode
             return ti.rc;
         }
 
-        if ti.indv.get_id().is_empty() || ti.indv.get_id().len() < 2 {
+        if is_invalid_id(ti.indv.get_id()) {
+            continue;
+        }
+
+        if let Some(version) = ti.version {
+            if checked.insert(ti.indv.get_id()) && is_stored_version_changed(ti.indv.get_id(), version) {
+                error!("commit: individual {} was changed after read, expected {:?}", ti.indv.get_id(), version);
+                return ResultCode::DatabaseModifiedError;
+            }
+        }
+    }
+
+    for ti in tnx.queue.iter() {
+        if ti.cmd == IndvOp::Remove && ti.indv.get_id().is_empty() {
+            continue;
+        }
+
+        if is_invalid_id(ti.indv.get_id()) {
             warn!("skip individual with invalid id: {}", ti.indv.to_string());
             continue;
         }
 
         debug!("commit {}", &ti.indv);
 
-        match api_client.update_use_param(&ti.ticket_id, &tnx.event_id, &tnx.src, ALL_MODULES, ti.cmd.clone(), &ti.indv) {
-            Ok(res) => {
-                if res.result != ResultCode::Ok {
-                    error!("commit: op_id={}, code={:?}", res.op_id, res.result);
-                    return res.result;
-                }
-            },
-            Err(e) => {
-                return e.result;
-            },
+        let rc = sink.update(&ti.ticket_id, &tnx.event_id, &tnx.src, ti.cmd.clone(), &ti.indv);
+        if rc != ResultCode::Ok {
+            return rc;
         }
     }
 
     ResultCode::Ok
 }
+
+pub struct PredicateDiff {
+    pub predicate: String,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
+pub struct ItemDiff {
+    pub uri: String,
+    pub cmd: IndvOp,
+    pub rc: ResultCode,
+    pub predicates: Vec<PredicateDiff>,
+}
+
+fn value2str(value: &Value) -> String {
+    match value {
+        Value::Num(m, e) => format!("{}e{}", m, e),
+        Value::Int(i) => i.to_string(),
+        Value::Datetime(i) => i.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Str(s, l) => {
+            if l.is_some() {
+                format!("{}@{}", s, l.to_string())
+            } else {
+                s.to_owned()
+            }
+        },
+        Value::Uri(s) => s.to_owned(),
+        _ => String::default(),
+    }
+}
+
+fn individual2values(indv: &mut Individual) -> HashMap<String, Vec<String>> {
+    let mut res = HashMap::new();
+
+    indv.parse_all();
+    for (predicate, resources) in indv.get_obj().get_resources() {
+        res.insert(predicate.to_owned(), resources.iter().map(|r| value2str(&r.value)).collect());
+    }
+
+    res
+}
+
+pub fn diff_individuals(before: &mut Individual, after: &mut Individual) -> Vec<PredicateDiff> {
+    let before = individual2values(before);
+    let after = individual2values(after);
+    let empty = vec![];
+
+    let predicates: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+
+    let mut res = vec![];
+    for predicate in predicates {
+        let b = before.get(predicate).unwrap_or(&empty);
+        let a = after.get(predicate).unwrap_or(&empty);
+
+        let removed: Vec<String> = b.iter().filter(|v| !a.contains(v)).cloned().collect();
+        let added: Vec<String> = a.iter().filter(|v| !b.contains(v)).cloned().collect();
+
+        if !removed.is_empty() || !added.is_empty() {
+            res.push(PredicateDiff {
+                predicate: predicate.to_owned(),
+                removed,
+                added,
+            });
+        }
+    }
+
+    res
+}
+
+// Err with the transaction code if the transaction already failed, as commit() would return it
+pub fn commit_dry_run(tnx: &Transaction) -> Result<Vec<ItemDiff>, ResultCode> {
+    if tnx.rc != ResultCode::Ok {
+        return Err(tnx.rc);
+    }
+
+    let mut res = vec![];
+
+    for ti in tnx.queue.iter() {
+        if ti.indv.get_id().is_empty() || ti.indv.get_id().len() < 2 {
+            continue;
+        }
+
+        let mut before = match read_individual(ti.indv.get_id()) {
+            Ok(indv) => indv,
+            Err(ResultCode::NotFound) => Individual::default(),
+            Err(rc) => {
+                // without the stored individual there is nothing to compare with
+                warn!("commit_dry_run: failed to read stored individual {}, code={:?}", ti.indv.get_id(), rc);
+                res.push(ItemDiff {
+                    uri: ti.indv.get_id().to_owned(),
+                    cmd: ti.cmd.clone(),
+                    rc,
+                    predicates: vec![],
+                });
+                continue;
+            },
+        };
+
+        let mut after = if ti.cmd == IndvOp::Remove {
+            Individual::default()
+        } else {
+            Individual::new_from_obj(ti.indv.get_obj())
+        };
+
+        res.push(ItemDiff {
+            uri: ti.indv.get_id().to_owned(),
+            cmd: ti.cmd.clone(),
+            rc: ti.rc,
+            predicates: diff_individuals(&mut before, &mut after),
+        });
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::individual_reader::{set_individual_reader, MemoryIndividualReader, TEST_READER_LOCK};
+    use crate::transaction_sink::MemorySink;
+    use std::sync::MutexGuard;
+    use v_individual_model::onto::datatype::Lang;
+
+    fn doc(id: &str, label: &str, counter: Option<i64>) -> Individual {
+        let mut indv = Individual::default();
+        indv.set_id(id);
+        indv.add_uri("rdf:type", "v-s:Document");
+        indv.add_string("rdfs:label", label, Lang::none());
+        if let Some(c) = counter {
+            indv.add_integer("v-s:updateCounter", c);
+        }
+        indv
+    }
+
+    fn set_stored(individuals: &[Individual]) {
+        let mut reader = MemoryIndividualReader::default();
+        for indv in individuals.iter() {
+            reader.add(indv);
+        }
+        set_individual_reader(Box::new(reader));
+    }
+
+    fn lock_reader() -> MutexGuard<'static, ()> {
+        TEST_READER_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // d:new is put as is, d:doc_1 is changed with SetIn and so remembers its stored version
+    fn tnx_with_set_in() -> Transaction {
+        let mut tnx = Transaction::default();
+        assert_eq!(tnx.add_to_transaction(IndvOp::Put, doc("d:new", "new", None), "t".to_owned(), "u".to_owned()), ResultCode::Ok);
+
+        let mut change = Individual::default();
+        change.set_id("d:doc_1");
+        change.add_string("rdfs:label", "b", Lang::none());
+        assert_eq!(tnx.add_to_transaction(IndvOp::SetIn, change, "t".to_owned(), "u".to_owned()), ResultCode::Ok);
+        tnx
+    }
+
+    #[test]
+    fn fnv1a_64_reference_values() {
+        assert_eq!(fnv1a_64(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a_64(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn stored_version_by_counter_or_content() {
+        assert_eq!(get_stored_version(&mut doc("d:doc_1", "a", Some(3))), StoredVersion::UpdateCounter(3));
+
+        let v1 = get_stored_version(&mut doc("d:doc_1", "a", None));
+        assert_eq!(v1, get_stored_version(&mut doc("d:doc_1", "a", None)));
+        assert_ne!(v1, get_stored_version(&mut doc("d:doc_1", "b", None)));
+    }
+
+    #[test]
+    fn commit_unchanged_version() {
+        let _lock = lock_reader();
+        set_stored(&[doc("d:doc_1", "a", Some(1))]);
+
+        let tnx = tnx_with_set_in();
+        let mut sink = MemorySink::default();
+        assert_eq!(commit(&tnx, &mut sink), ResultCode::Ok);
+        assert_eq!(sink.individuals.len(), 2);
+    }
+
+    #[test]
+    fn commit_conflict_on_moved_counter() {
+        let _lock = lock_reader();
+        set_stored(&[doc("d:doc_1", "a", Some(1))]);
+        let tnx = tnx_with_set_in();
+
+        set_stored(&[doc("d:doc_1", "a", Some(2))]);
+        let mut sink = MemorySink::default();
+        assert_eq!(commit(&tnx, &mut sink), ResultCode::DatabaseModifiedError);
+        // d:new comes first in the queue and is not written either
+        assert!(sink.individuals.is_empty());
+    }
+
+    #[test]
+    fn commit_conflict_on_changed_content() {
+        let _lock = lock_reader();
+        set_stored(&[doc("d:doc_1", "a", None)]);
+        let tnx = tnx_with_set_in();
+
+        set_stored(&[doc("d:doc_1", "c", None)]);
+        let mut sink = MemorySink::default();
+        assert_eq!(commit(&tnx, &mut sink), ResultCode::DatabaseModifiedError);
+        assert!(sink.individuals.is_empty());
+    }
+
+    #[test]
+    fn max_items_fails_transaction() {
+        let mut tnx = Transaction::default();
+        tnx.max_items = 1;
+
+        assert_eq!(tnx.add_to_transaction(IndvOp::Put, doc("d:doc_1", "a", None), "t".to_owned(), "u".to_owned()), ResultCode::Ok);
+        assert_eq!(tnx.add_to_transaction(IndvOp::Put, doc("d:doc_2", "b", None), "t".to_owned(), "u".to_owned()), ResultCode::SizeTooLarge);
+        assert_eq!(tnx.rc, ResultCode::SizeTooLarge);
+        assert_eq!(tnx.queue.len(), 1);
+
+        // the transaction stays failed, further writes and the commit are refused
+        assert_eq!(tnx.add_to_transaction(IndvOp::Put, doc("d:doc_3", "c", None), "t".to_owned(), "u".to_owned()), ResultCode::SizeTooLarge);
+        let mut sink = MemorySink::default();
+        assert_eq!(commit(&tnx, &mut sink), ResultCode::SizeTooLarge);
+        assert!(sink.individuals.is_empty());
+    }
+
+    #[test]
+    fn max_size_fails_transaction() {
+        let indv = doc("d:doc_1", "a", None);
+        let size = indv.get_obj().as_json().to_string().len();
+
+        let mut tnx = Transaction::default();
+        tnx.max_size = size;
+        assert_eq!(tnx.add_to_transaction(IndvOp::Put, indv, "t".to_owned(), "u".to_owned()), ResultCode::Ok);
+        assert_eq!(tnx.add_to_transaction(IndvOp::Put, doc("d:doc_2", "b", None), "t".to_owned(), "u".to_owned()), ResultCode::SizeTooLarge);
+        assert_eq!(tnx.rc, ResultCode::SizeTooLarge);
+        assert_eq!(tnx.queue.len(), 1);
+
+        let mut sink = MemorySink::default();
+        assert_eq!(commit(&tnx, &mut sink), ResultCode::SizeTooLarge);
+        assert!(sink.individuals.is_empty());
+    }
+}
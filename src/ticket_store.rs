@@ -0,0 +1,110 @@
+use chrono::{NaiveDateTime, Utc};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use v_common::v_api::common_type::ResultCode;
+use v_individual_model::onto::individual::Individual;
+use v_individual_model::onto::parser::parse_raw;
+use v_storage::{StorageId, StorageMode, VStorage};
+
+pub trait TicketStore {
+    // Returns the user of a valid ticket, NotAuthorized if the ticket is unknown or TicketExpired
+    fn get_user(&mut self, ticket_id: &str) -> Result<String, ResultCode>;
+}
+
+pub struct LmdbTicketStore {
+    storage: VStorage,
+}
+
+impl LmdbTicketStore {
+    pub fn new(storage: VStorage) -> Self {
+        Self {
+            storage,
+        }
+    }
+}
+
+impl TicketStore for LmdbTicketStore {
+    fn get_user(&mut self, ticket_id: &str) -> Result<String, ResultCode> {
+        let mut indv = Individual::default();
+        if !self.storage.get_individual_from_db(StorageId::Tickets, ticket_id, &mut indv) || parse_raw(&mut indv).is_err() {
+            return Err(ResultCode::NotAuthorized);
+        }
+        indv.parse_all();
+
+        let user_uri = indv.get_first_literal("ticket:accessor").ok_or(ResultCode::NotAuthorized)?;
+        let when = indv.get_first_literal("ticket:when").ok_or(ResultCode::NotAuthorized)?;
+        let duration = indv.get_first_literal("ticket:duration").and_then(|d| d.parse::<i64>().ok()).ok_or(ResultCode::NotAuthorized)?;
+
+        let start_time = NaiveDateTime::parse_from_str(when.trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S%.f").map_err(|_| ResultCode::NotAuthorized)?.timestamp();
+        if start_time + duration < Utc::now().timestamp() {
+            return Err(ResultCode::TicketExpired);
+        }
+
+        Ok(user_uri)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryTicketStore {
+    // ticket id -> (user, unix time the ticket expires at)
+    pub tickets: HashMap<String, (String, i64)>,
+}
+
+impl MemoryTicketStore {
+    pub fn add(&mut self, ticket_id: &str, user_uri: &str, end_time: i64) {
+        self.tickets.insert(ticket_id.to_owned(), (user_uri.to_owned(), end_time));
+    }
+}
+
+impl TicketStore for MemoryTicketStore {
+    fn get_user(&mut self, ticket_id: &str) -> Result<String, ResultCode> {
+        match self.tickets.get(ticket_id) {
+            Some((_, end_time)) if *end_time < Utc::now().timestamp() => Err(ResultCode::TicketExpired),
+            Some((user_uri, _)) => Ok(user_uri.to_owned()),
+            None => Err(ResultCode::NotAuthorized),
+        }
+    }
+}
+
+lazy_static! {
+    static ref TICKET_STORE: Mutex<RefCell<Option<Box<dyn TicketStore + Send>>>> = Mutex::new(RefCell::new(None));
+}
+
+pub fn set_ticket_store(store: Box<dyn TicketStore + Send>) {
+    let mut sh_store = TICKET_STORE.lock().unwrap();
+    *sh_store.get_mut() = Some(store);
+}
+
+// Without a store set by the embedder the tickets of the local LMDB database are used
+pub fn read_ticket_user(ticket_id: &str) -> Result<String, ResultCode> {
+    let mut sh_store = TICKET_STORE.lock().unwrap();
+    sh_store.get_mut().get_or_insert_with(|| Box::new(LmdbTicketStore::new(VStorage::new_lmdb("./data", StorageMode::ReadOnly, None)))).get_user(ticket_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_ticket_store() {
+        let now = Utc::now().timestamp();
+        let mut store = MemoryTicketStore::default();
+        store.add("t:valid", "d:user_1", now + 3600);
+        store.add("t:expired", "d:user_2", now - 1);
+
+        assert_eq!(store.get_user("t:valid"), Ok("d:user_1".to_owned()));
+        assert_eq!(store.get_user("t:expired"), Err(ResultCode::TicketExpired));
+        assert_eq!(store.get_user("t:unknown"), Err(ResultCode::NotAuthorized));
+    }
+
+    #[test]
+    fn read_ticket_user_from_configured_store() {
+        let mut store = MemoryTicketStore::default();
+        store.add("t:valid", "d:user_1", Utc::now().timestamp() + 3600);
+        set_ticket_store(Box::new(store));
+
+        assert_eq!(read_ticket_user("t:valid"), Ok("d:user_1".to_owned()));
+        assert_eq!(read_ticket_user("t:unknown"), Err(ResultCode::NotAuthorized));
+    }
+}
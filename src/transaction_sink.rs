@@ -0,0 +1,104 @@
+use crate::common::num2decimal;
+use chrono::{TimeZone, Utc};
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use v_common::v_api::api_client::{IndvOp, MStorageClient, ALL_MODULES};
+use v_common::v_api::common_type::ResultCode;
+use v_individual_model::onto::individual::Individual;
+use v_individual_model::onto::resource::Value;
+
+pub trait TransactionSink {
+    fn update(&mut self, ticket_id: &str, event_id: &str, src: &str, cmd: IndvOp, indv: &Individual) -> ResultCode;
+}
+
+impl TransactionSink for MStorageClient {
+    fn update(&mut self, ticket_id: &str, event_id: &str, src: &str, cmd: IndvOp, indv: &Individual) -> ResultCode {
+        match self.update_use_param(ticket_id, event_id, src, ALL_MODULES, cmd, indv) {
+            Ok(res) => {
+                if res.result != ResultCode::Ok {
+                    error!("commit: op_id={}, code={:?}", res.op_id, res.result);
+                }
+                res.result
+            },
+            Err(e) => e.result,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemorySink {
+    pub individuals: HashMap<String, Individual>,
+}
+
+impl TransactionSink for MemorySink {
+    fn update(&mut self, _ticket_id: &str, _event_id: &str, _src: &str, cmd: IndvOp, indv: &Individual) -> ResultCode {
+        if cmd == IndvOp::Remove {
+            self.individuals.remove(indv.get_id());
+        } else {
+            self.individuals.insert(indv.get_id().to_owned(), Individual::new_from_obj(indv.get_obj()));
+        }
+        ResultCode::Ok
+    }
+}
+
+pub struct FileSink {
+    file: File,
+}
+
+impl FileSink {
+    pub fn new(path: &str) -> io::Result<Self> {
+        Ok(Self {
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+        })
+    }
+}
+
+fn value2jsonld(value: &Value) -> Option<JsonValue> {
+    match value {
+        Value::Num(m, e) => Some(json!({"@value": num2decimal(*m, *e).to_string(), "@type": "xsd:decimal"})),
+        Value::Int(i) => Some(json!({"@value": i, "@type": "xsd:integer"})),
+        Value::Datetime(i) => Some(json!({"@value": Utc.timestamp(*i, 0).to_rfc3339(), "@type": "xsd:dateTime"})),
+        Value::Bool(b) => Some(json!({"@value": b})),
+        Value::Str(s, l) => {
+            if l.is_some() {
+                Some(json!({"@value": s, "@language": l.to_string().to_lowercase()}))
+            } else {
+                Some(json!({"@value": s}))
+            }
+        },
+        Value::Uri(s) => Some(json!({"@id": s})),
+        _ => None,
+    }
+}
+
+pub fn individual2jsonld(indv: &mut Individual) -> JsonValue {
+    let mut res = Map::new();
+    res.insert("@id".to_owned(), json!(indv.get_id()));
+
+    indv.parse_all();
+    for (predicate, resources) in indv.get_obj().get_resources() {
+        let values: Vec<JsonValue> = resources.iter().filter_map(|r| value2jsonld(&r.value)).collect();
+        res.insert(predicate.to_owned(), JsonValue::Array(values));
+    }
+
+    JsonValue::Object(res)
+}
+
+impl TransactionSink for FileSink {
+    fn update(&mut self, _ticket_id: &str, _event_id: &str, _src: &str, cmd: IndvOp, indv: &Individual) -> ResultCode {
+        let out = if cmd == IndvOp::Remove {
+            json!({"@id": indv.get_id(), "v-s:deleted": [{"@value": true}]})
+        } else {
+            individual2jsonld(&mut Individual::new_from_obj(indv.get_obj()))
+        };
+
+        if let Err(e) = writeln!(self.file, "{}", out) {
+            error!("file sink: fail write {}, err={:?}", indv.get_id(), e);
+            return ResultCode::FailStore;
+        }
+        ResultCode::Ok
+    }
+}